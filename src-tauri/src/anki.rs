@@ -0,0 +1,191 @@
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use serde_json::json;
+use std::{
+  fs,
+  io::Write,
+  path::{Path, PathBuf},
+};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::flashcards::Card;
+
+// Fixed so repeated exports reuse one note type in the user's collection.
+const MODEL_ID: i64 = 1_718_000_000_001;
+const MODEL_NAME: &str = "Applesauce Basic";
+// Anki's separator between a note's fields.
+const FIELD_SEP: char = '\u{1f}';
+
+// Legacy (schema 11) collection layout, which every Anki version imports.
+const SCHEMA: &str = "
+CREATE TABLE col (
+  id integer primary key, crt integer not null, mod integer not null,
+  scm integer not null, ver integer not null, dty integer not null,
+  usn integer not null, ls integer not null, conf text not null,
+  models text not null, decks text not null, dconf text not null, tags text not null
+);
+CREATE TABLE notes (
+  id integer primary key, guid text not null, mid integer not null,
+  mod integer not null, usn integer not null, tags text not null,
+  flds text not null, sfld integer not null, csum integer not null,
+  flags integer not null, data text not null
+);
+CREATE TABLE cards (
+  id integer primary key, nid integer not null, did integer not null,
+  ord integer not null, mod integer not null, usn integer not null,
+  type integer not null, queue integer not null, due integer not null,
+  ivl integer not null, factor integer not null, reps integer not null,
+  lapses integer not null, left integer not null, odue integer not null,
+  odid integer not null, flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+  id integer primary key, cid integer not null, usn integer not null,
+  ease integer not null, ivl integer not null, lastIvl integer not null,
+  factor integer not null, time integer not null, type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+";
+
+// Anki fields are HTML.
+fn html_escape(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('\n', "<br>")
+}
+
+fn sha1_hex(s: &str) -> String {
+  sha1_smol::Sha1::from(s).digest().to_string()
+}
+
+// Anki's duplicate-check checksum: first 8 hex digits of the sort field's SHA-1.
+fn field_checksum(s: &str) -> i64 {
+  i64::from_str_radix(&sha1_hex(s)[..8], 16).unwrap_or(0)
+}
+
+// Stable per session name, so re-exporting updates the same deck.
+fn deck_id(deck_name: &str) -> i64 {
+  1_000_000_000 + i64::from_str_radix(&sha1_hex(deck_name)[..7], 16).unwrap_or(0)
+}
+
+fn model_json(deck_id: i64, now: i64) -> serde_json::Value {
+  let field = |name: &str, ord: u32| {
+    json!({ "name": name, "ord": ord, "sticky": false, "rtl": false,
+            "font": "Arial", "size": 20, "media": [] })
+  };
+  json!({ MODEL_ID.to_string(): {
+    "id": MODEL_ID, "name": MODEL_NAME, "type": 0, "mod": now, "usn": -1,
+    "sortf": 0, "did": deck_id, "tags": [], "vers": [],
+    "flds": [field("Front", 0), field("Back", 1)],
+    "tmpls": [{
+      "name": "Card 1", "ord": 0, "qfmt": "{{Front}}",
+      "afmt": "{{FrontSide}}<hr id=answer>{{Back}}",
+      "did": null, "bqfmt": "", "bafmt": "", "bfont": "", "bsize": 0
+    }],
+    "css": ".card { font-family: arial; font-size: 20px; text-align: center; }",
+    "latexPre": "\\documentclass[12pt]{article}\n\\begin{document}\n",
+    "latexPost": "\\end{document}",
+    "req": [[0, "all", [0]]]
+  }})
+}
+
+fn decks_json(deck_id: i64, deck_name: &str, now: i64) -> serde_json::Value {
+  let deck = |id: i64, name: &str| {
+    json!({ "id": id, "name": name, "desc": "", "mod": now, "usn": -1,
+            "collapsed": false, "dyn": 0, "conf": 1, "extendNew": 10, "extendRev": 50,
+            "newToday": [0, 0], "revToday": [0, 0], "lrnToday": [0, 0], "timeToday": [0, 0] })
+  };
+  json!({ "1": deck(1, "Default"), deck_id.to_string(): deck(deck_id, deck_name) })
+}
+
+fn dconf_json() -> serde_json::Value {
+  json!({ "1": {
+    "id": 1, "name": "Default", "mod": 0, "usn": 0, "dyn": false,
+    "maxTaken": 60, "timer": 0, "autoplay": true, "replayq": true,
+    "new": { "delays": [1, 10], "ints": [1, 4, 7], "initialFactor": 2500,
+             "order": 1, "perDay": 20, "bury": true, "separate": true },
+    "rev": { "perDay": 100, "ease4": 1.3, "fuzz": 0.05, "ivlFct": 1,
+             "maxIvl": 36500, "bury": true, "minSpace": 1 },
+    "lapse": { "delays": [10], "mult": 0, "minInt": 1, "leechFails": 8, "leechAction": 0 }
+  }})
+}
+
+fn write_collection(db: &Path, deck_name: &str, cards: &[Card]) -> Result<()> {
+  let now_ms = chrono::Local::now().timestamp_millis();
+  let now = now_ms / 1000;
+  let did = deck_id(deck_name);
+  let conf = json!({ "nextPos": 1, "estTimes": true, "activeDecks": [1], "sortType": "noteFld",
+                     "timeLim": 0, "sortBackwards": false, "addToCur": true, "curDeck": did,
+                     "newBury": true, "newSpread": 0, "dueCounts": true, "curModel": MODEL_ID,
+                     "collapseTime": 1200 });
+
+  let mut conn = Connection::open(db)?;
+  conn.execute_batch(SCHEMA)?;
+  let tx = conn.transaction()?;
+  tx.execute(
+    "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+    params![
+      now,
+      now_ms,
+      conf.to_string(),
+      model_json(did, now).to_string(),
+      decks_json(did, deck_name, now).to_string(),
+      dconf_json().to_string(),
+    ],
+  )?;
+  for (i, card) in cards.iter().enumerate() {
+    // Note and card ids only need to be unique; Anki uses creation millis.
+    let id = now_ms + i as i64;
+    let front = html_escape(&card.term);
+    let back = html_escape(&card.definition);
+    tx.execute(
+      "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, '', ?5, ?6, ?7, 0, '')",
+      params![
+        id,
+        uuid::Uuid::new_v4().simple().to_string(),
+        MODEL_ID,
+        now,
+        format!("{front}{FIELD_SEP}{back}"),
+        card.term,
+        field_checksum(&card.term),
+      ],
+    )?;
+    tx.execute(
+      "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+      params![id, did, now, i as i64 + 1],
+    )?;
+  }
+  tx.commit()?;
+  Ok(())
+}
+
+/// Builds `<session_id>.apkg` in `dir`: one Basic (Front/Back) note per card
+/// in a deck named after the session.
+pub fn write_apkg(dir: &Path, session_id: &str, cards: &[Card]) -> Result<PathBuf> {
+  let db = std::env::temp_dir().join(format!(
+    "applesauce-{session_id}-{}.anki2",
+    chrono::Local::now().timestamp_millis()
+  ));
+  let packaged = (|| {
+    write_collection(&db, session_id, cards)?;
+    let path = dir.join(format!("{session_id}.apkg"));
+    let mut zip = ZipWriter::new(fs::File::create(&path)?);
+    let options = SimpleFileOptions::default();
+    zip.start_file("collection.anki2", options)?;
+    zip.write_all(&fs::read(&db)?)?;
+    // No media, but Anki expects the (empty) map to exist.
+    zip.start_file("media", options)?;
+    zip.write_all(b"{}")?;
+    zip.finish()?;
+    Ok(path)
+  })();
+  fs::remove_file(&db).ok();
+  packaged
+}
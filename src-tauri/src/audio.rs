@@ -0,0 +1,277 @@
+use anyhow::{anyhow, Result};
+use cpal::Sample;
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use std::{
+  ffi::OsStr,
+  fs::OpenOptions,
+  io::{Read, Seek, SeekFrom, Write},
+  path::Path,
+};
+
+use crate::tools::{require_tool, run_tool};
+
+/// Whisper only accepts 16 kHz mono f32.
+pub const WHISPER_RATE: u32 = 16_000;
+
+/// Reads a WAV as interleaved f32 in -1.0..=1.0, whatever its bit depth.
+pub fn read_wav_f32(path: &Path) -> Result<(WavSpec, Vec<f32>)> {
+  let mut reader = WavReader::open(path)?;
+  let spec = reader.spec();
+  let samples = match spec.sample_format {
+    SampleFormat::Float => reader.samples::<f32>().collect::<hound::Result<Vec<_>>>()?,
+    SampleFormat::Int => {
+      let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+      reader
+        .samples::<i32>()
+        .map(|s| s.map(|v| v as f32 / scale))
+        .collect::<hound::Result<Vec<_>>>()?
+    }
+  };
+  Ok((spec, samples))
+}
+
+/// Writes one f32 sample in whatever format `spec` says, clamping ints.
+pub fn write_sample<W: Write + Seek>(
+  writer: &mut WavWriter<W>,
+  spec: &WavSpec,
+  s: f32,
+) -> hound::Result<()> {
+  match (spec.sample_format, spec.bits_per_sample) {
+    (SampleFormat::Float, _) => writer.write_sample(s),
+    (_, 24) => writer.write_sample((s.clamp(-1.0, 1.0) * 8_388_607.0) as i32),
+    _ => writer.write_sample(i16::from_sample(s)),
+  }
+}
+
+/// Writes interleaved f32 samples as a WAV with the given format.
+pub fn write_wav_f32(path: &Path, spec: WavSpec, samples: &[f32]) -> Result<()> {
+  let mut writer = WavWriter::create(path, spec)?;
+  for &s in samples {
+    write_sample(&mut writer, &spec, s)?;
+  }
+  writer.finalize()?;
+  Ok(())
+}
+
+/// Averages interleaved channels down to one.
+pub fn to_mono(samples: &[f32], channels: u16) -> Vec<f32> {
+  let channels = channels.max(1) as usize;
+  if channels == 1 {
+    return samples.to_vec();
+  }
+  samples
+    .chunks_exact(channels)
+    .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+    .collect()
+}
+
+/// Linear-interpolation resampler for mono audio. Plenty for speech going
+/// into Whisper; not meant for music.
+pub fn resample_linear(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+  if from == to || samples.is_empty() {
+    return samples.to_vec();
+  }
+  let ratio = from as f64 / to as f64;
+  let out_len = (samples.len() as f64 / ratio).floor() as usize;
+  (0..out_len)
+    .map(|i| {
+      let pos = i as f64 * ratio;
+      let idx = pos as usize;
+      let frac = (pos - idx as f64) as f32;
+      let a = samples[idx];
+      let b = *samples.get(idx + 1).unwrap_or(&a);
+      a + (b - a) * frac
+    })
+    .collect()
+}
+
+/// Streaming version of `resample_linear` for interleaved audio that arrives
+/// a frame at a time, as in the recorder. The position carries over between
+/// calls, so block boundaries neither click nor drift, and the output length
+/// tracks the input's duration exactly.
+pub struct FrameResampler {
+  // Input frames per output frame.
+  step: f64,
+  // Where the next output frame falls, in input frames after `prev`.
+  pos: f64,
+  prev: Option<Vec<f32>>,
+}
+
+impl FrameResampler {
+  pub fn new(from: u32, to: u32) -> Self {
+    Self { step: from.max(1) as f64 / to.max(1) as f64, pos: 0.0, prev: None }
+  }
+
+  /// Feeds one input frame and appends the output frames that fall between
+  /// it and the previous one to `out` (none, one or several).
+  pub fn push(&mut self, frame: &[f32], out: &mut Vec<f32>) {
+    let Some(prev) = &mut self.prev else {
+      self.prev = Some(frame.to_vec());
+      return;
+    };
+    while self.pos < 1.0 {
+      let frac = self.pos as f32;
+      out.extend(prev.iter().zip(frame).map(|(&a, &b)| a + (b - a) * frac));
+      self.pos += self.step;
+    }
+    self.pos -= 1.0;
+    prev.copy_from_slice(frame);
+  }
+
+  /// Forgets the last frame, for when the input jumps (e.g. after a pause).
+  pub fn reset(&mut self) {
+    self.pos = 0.0;
+    self.prev = None;
+  }
+}
+
+/// Converts interleaved audio between channel counts and sample rates.
+/// Mono fans out to every channel, other layouts are averaged to mono first;
+/// each channel is then resampled with `resample_linear`.
+pub fn convert_format(samples: &[f32], from: &WavSpec, to: &WavSpec) -> Vec<f32> {
+  let (from_ch, to_ch) = (from.channels.max(1) as usize, to.channels.max(1) as usize);
+  let planes: Vec<Vec<f32>> = if from_ch == to_ch {
+    (0..to_ch)
+      .map(|c| samples.iter().skip(c).step_by(to_ch).copied().collect())
+      .collect()
+  } else {
+    vec![to_mono(samples, from.channels); to_ch]
+  };
+  let planes: Vec<Vec<f32>> = planes
+    .iter()
+    .map(|p| resample_linear(p, from.sample_rate, to.sample_rate))
+    .collect();
+  let frames = planes.iter().map(Vec::len).min().unwrap_or(0);
+  (0..frames)
+    .flat_map(|i| planes.iter().map(move |p| p[i]))
+    .collect()
+}
+
+// Has ffmpeg decode anything else (MP3/FLAC recordings) to 16 kHz mono s16.
+fn decode_with_ffmpeg(path: &Path) -> Result<Vec<f32>> {
+  let ffmpeg = require_tool("ffmpeg", "install ffmpeg to transcribe compressed audio")?;
+  let rate = WHISPER_RATE.to_string();
+  let args: Vec<&OsStr> = ["-hide_banner", "-loglevel", "error", "-i"]
+    .map(OsStr::new)
+    .into_iter()
+    .chain([path.as_os_str()])
+    .chain(["-vn", "-ac", "1", "-ar", &rate, "-f", "s16le", "pipe:1"].map(OsStr::new))
+    .collect();
+  let pcm = run_tool(&ffmpeg, args)?;
+  Ok(
+    pcm
+      .chunks_exact(2)
+      .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32_768.0)
+      .collect(),
+  )
+}
+
+/// Checks that a WAV has a readable header, a sane format and at least one
+/// sample. A writer that hasn't been finalized leaves the data length at
+/// zero, which is what this catches. Other formats are left to ffmpeg.
+pub fn validate_wav(path: &Path) -> Result<()> {
+  let is_wav = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("wav"));
+  if !is_wav {
+    return Ok(());
+  }
+  let not_finalized = |why: String| anyhow!("recording not finalized: {} {why}", path.display());
+  let reader = WavReader::open(path).map_err(|e| not_finalized(format!("is unreadable ({e})")))?;
+  let spec = reader.spec();
+  if spec.channels == 0 || spec.sample_rate == 0 || spec.bits_per_sample == 0 {
+    return Err(not_finalized(format!(
+      "has a bad header ({} ch, {} Hz, {} bit)",
+      spec.channels, spec.sample_rate, spec.bits_per_sample
+    )));
+  }
+  if reader.len() == 0 {
+    return Err(not_finalized("has no samples".into()));
+  }
+  Ok(())
+}
+
+/// Points the RIFF and `data` chunk sizes of a WAV at whatever is actually on
+/// disk, for a file whose writer never finalized (the app was killed while
+/// recording). Only for files known to be unfinished: anything after the
+/// data chunk is taken as audio. Returns whether the header changed.
+pub fn repair_wav_header(path: &Path) -> Result<bool> {
+  let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+  let len = file.metadata()?.len();
+  let mut riff = [0u8; 12];
+  file.read_exact(&mut riff)?;
+  if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+    return Err(anyhow!("{} is not a WAV file", path.display()));
+  }
+  let mut block_align = 1_u64;
+  let mut pos = 12_u64;
+  while pos + 8 <= len {
+    file.seek(SeekFrom::Start(pos))?;
+    let mut chunk = [0u8; 8];
+    file.read_exact(&mut chunk)?;
+    let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+    let body = pos + 8;
+    match &chunk[0..4] {
+      b"fmt " => {
+        let mut fmt = [0u8; 16];
+        file.read_exact(&mut fmt)?;
+        block_align = u16::from_le_bytes([fmt[12], fmt[13]]).max(1) as u64;
+      }
+      b"data" => {
+        // Whole frames only; a frame cut off by the kill is dropped.
+        let on_disk = (len - body) / block_align * block_align;
+        if size == on_disk {
+          return Ok(false);
+        }
+        file.seek(SeekFrom::Start(pos + 4))?;
+        file.write_all(&(on_disk as u32).to_le_bytes())?;
+        file.seek(SeekFrom::Start(4))?;
+        file.write_all(&((body + on_disk - 8) as u32).to_le_bytes())?;
+        return Ok(true);
+      }
+      _ => {}
+    }
+    // Chunks are padded to an even length.
+    pos = body + size + (size & 1);
+  }
+  Err(anyhow!("{} has no data chunk", path.display()))
+}
+
+/// Loads a recording and converts it to what Whisper wants (16 kHz mono
+/// f32). Non-WAV files are decoded with ffmpeg.
+pub fn load_for_whisper(path: &Path) -> Result<Vec<f32>> {
+  let is_wav = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("wav"));
+  if !is_wav {
+    return decode_with_ffmpeg(path);
+  }
+  let (spec, samples) = read_wav_f32(path)?;
+  if spec.sample_rate == 0 {
+    return Err(anyhow!("{} has a zero sample rate", path.display()));
+  }
+  let mono = to_mono(&samples, spec.channels);
+  Ok(resample_linear(&mono, spec.sample_rate, WHISPER_RATE))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn frame_resampler_keeps_a_sine_in_pitch_and_level() {
+    let (from, to, freq, amplitude) = (48_000, 16_000, 1_000.0, 0.8);
+    let input: Vec<f32> = (0..from)
+      .map(|i| amplitude * (i as f32 * freq * std::f32::consts::TAU / from as f32).sin())
+      .collect();
+    let mut resampler = FrameResampler::new(from, to);
+    let mut out = Vec::new();
+    // Fed a frame at a time, the way the recorder does.
+    for s in &input {
+      resampler.push(std::slice::from_ref(s), &mut out);
+    }
+
+    // One second in, one second out.
+    assert!(out.len().abs_diff(to as usize) <= 1, "{} samples", out.len());
+    let rising = out.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+    assert!(rising.abs_diff(freq as usize) <= 1, "{rising} cycles");
+    let peak = out.iter().fold(0.0_f32, |m, s| m.max(s.abs()));
+    assert!((peak - amplitude).abs() < 0.03 * amplitude, "peak {peak}");
+  }
+}
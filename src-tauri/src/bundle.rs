@@ -0,0 +1,150 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+  fs::{self, File},
+  io::{self, Write},
+  path::{Path, PathBuf},
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::imports::register_saved_audio;
+use crate::recorder::new_session_id;
+use crate::sessions::{
+  is_recording, read_meta, reserve_stem, session_files, session_summary, validate_session_id,
+  write_meta, SessionSummary,
+};
+
+/// Marks a zip as an Applesauce session bundle and lists what's in it.
+pub const MANIFEST_NAME: &str = "applesauce-bundle.json";
+pub const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+  pub version: u32,
+  pub session_id: String,
+  // File names inside the zip, all `<session_id>.<suffix>`.
+  pub files: Vec<String>,
+}
+
+/// Packs every file of a session (recording, metadata, transcript, notes,
+/// peaks, ...) plus a manifest into a zip. `dest` may be a folder, in which
+/// case the bundle is `<dest>/<session_id>.zip`. Files are streamed into
+/// the archive, so a long WAV is never held in memory. Returns the zip path.
+pub fn export_session_zip(dir: &Path, session_id: &str, dest: &Path) -> Result<PathBuf> {
+  validate_session_id(session_id)?;
+  let files = session_files(dir, session_id)?;
+  if !files.iter().any(|f| is_recording(f)) {
+    return Err(anyhow!("session '{session_id}' not found"));
+  }
+  let out = if dest.is_dir() { dest.join(format!("{session_id}.zip")) } else { dest.to_path_buf() };
+  let manifest = BundleManifest {
+    version: BUNDLE_VERSION,
+    session_id: session_id.to_string(),
+    files: files
+      .iter()
+      .filter_map(|f| f.file_name().and_then(|n| n.to_str()).map(str::to_string))
+      .collect(),
+  };
+
+  // Built next to the destination and renamed, so a failed export never
+  // leaves a half-written zip behind under the real name.
+  let tmp = out.with_extension("zip.tmp");
+  let written = (|| {
+    let mut zip = ZipWriter::new(File::create(&tmp)?);
+    let deflate = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(MANIFEST_NAME, deflate)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    for (path, name) in files.iter().zip(&manifest.files) {
+      // Audio barely deflates; storing it keeps big exports fast.
+      let options = match is_recording(path) {
+        true => SimpleFileOptions::default()
+          .compression_method(CompressionMethod::Stored)
+          .large_file(fs::metadata(path)?.len() >= u32::MAX as u64),
+        false => deflate,
+      };
+      zip.start_file(name.as_str(), options)?;
+      io::copy(&mut File::open(path)?, &mut zip)?;
+    }
+    zip.finish()?;
+    Ok::<_, anyhow::Error>(())
+  })();
+  if let Err(e) = written {
+    fs::remove_file(&tmp).ok();
+    return Err(e);
+  }
+  fs::rename(&tmp, &out)?;
+  Ok(out)
+}
+
+/// Unpacks a bundle made by `export_session_zip` into `dir` as a new session.
+/// It always gets a fresh id, so it can't collide with what's already there
+/// (including the session it was exported from). Zips without a manifest,
+/// or with files the manifest doesn't account for, are refused.
+pub fn import_session_zip(dir: &Path, zip_path: &Path) -> Result<SessionSummary> {
+  let not_a_bundle = || anyhow!("{} is not an Applesauce session bundle", zip_path.display());
+  let mut archive = ZipArchive::new(File::open(zip_path)?).map_err(|_| not_a_bundle())?;
+  let manifest: BundleManifest = {
+    let entry = archive.by_name(MANIFEST_NAME).map_err(|_| not_a_bundle())?;
+    serde_json::from_reader(entry).map_err(|_| not_a_bundle())?
+  };
+  if manifest.version > BUNDLE_VERSION {
+    return Err(anyhow!(
+      "bundle format {} is newer than this version of Applesauce understands",
+      manifest.version
+    ));
+  }
+  validate_session_id(&manifest.session_id)?;
+
+  // Every listed file has to be `<id>.<suffix>` and present; nothing else
+  // may be in the zip, and there has to be a recording.
+  let prefix = format!("{}.", manifest.session_id);
+  for name in &manifest.files {
+    let suffix = name.strip_prefix(&prefix).unwrap_or_default();
+    if suffix.is_empty() || suffix.contains(['/', '\\']) || suffix.contains("..") {
+      return Err(anyhow!("bundle lists an unexpected file: {name}"));
+    }
+    archive.by_name(name).map_err(|_| anyhow!("bundle is missing {name}"))?;
+  }
+  for i in 0..archive.len() {
+    let name = archive.by_index(i)?.name().to_string();
+    if name != MANIFEST_NAME && !manifest.files.contains(&name) {
+      return Err(anyhow!("bundle contains an unexpected file: {name}"));
+    }
+  }
+  let Some(recording) = manifest.files.iter().find(|n| is_recording(Path::new(n))) else {
+    return Err(anyhow!("bundle has no recording in it"));
+  };
+
+  fs::create_dir_all(dir)?;
+  let reserved = reserve_stem(dir, &new_session_id())?;
+  let new_id = reserved.id.clone();
+  let renamed = |name: &str| dir.join(format!("{new_id}{}", &name[manifest.session_id.len()..]));
+  let mut written = Vec::new();
+  let imported = (|| {
+    for name in &manifest.files {
+      let out = renamed(name);
+      let mut entry = archive.by_name(name)?;
+      written.push(out.clone());
+      io::copy(&mut entry, &mut File::create(&out)?)?;
+    }
+    // Only the metadata has the id inside; the rest go by file name.
+    match read_meta(dir, &new_id) {
+      Some(mut meta) => {
+        meta.session_id = new_id.clone();
+        // A copy, not the original recording.
+        meta.uid = None;
+        write_meta(dir, &meta)?;
+      }
+      None => {
+        register_saved_audio(dir, &new_id, &renamed(recording))?;
+      }
+    }
+    session_summary(dir, &new_id)
+  })();
+  if imported.is_err() {
+    for f in &written {
+      fs::remove_file(f).ok();
+    }
+  }
+  imported
+}
@@ -0,0 +1,181 @@
+use anyhow::{anyhow, Result};
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde::Serialize;
+use std::{thread, time::Duration};
+use tauri::{AppHandle, Emitter};
+
+pub const DEVICES_CHANGED_EVENT: &str = "devices://changed";
+// How often the watcher re-reads the device list.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+// Rates we probe against each supported range so the picker shows the
+// familiar values instead of just min/max.
+const COMMON_RATES: [u32; 8] = [8_000, 16_000, 22_050, 32_000, 44_100, 48_000, 88_200, 96_000];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceInfo {
+  pub name: String,
+  pub is_default: bool,
+  pub sample_rates: Vec<u32>,
+  pub channels: Vec<u16>,
+}
+
+/// Enumerates input devices on the default host. No devices is an empty
+/// list, not an error; only a failing host/backend is reported.
+pub fn list_input_devices() -> Result<Vec<DeviceInfo>> {
+  let host = cpal::default_host();
+  let default_name = host.default_input_device().and_then(|d| d.name().ok());
+
+  let mut out = Vec::new();
+  for device in host.input_devices()? {
+    // Devices that vanish or refuse to describe themselves are skipped.
+    let Ok(name) = device.name() else { continue };
+    let mut sample_rates = Vec::new();
+    let mut channels = Vec::new();
+    if let Ok(configs) = device.supported_input_configs() {
+      for range in configs {
+        let (min, max) = (range.min_sample_rate().0, range.max_sample_rate().0);
+        for rate in COMMON_RATES.iter().copied().chain([min, max]) {
+          if rate >= min && rate <= max && !sample_rates.contains(&rate) {
+            sample_rates.push(rate);
+          }
+        }
+        if !channels.contains(&range.channels()) {
+          channels.push(range.channels());
+        }
+      }
+    }
+    sample_rates.sort_unstable();
+    channels.sort_unstable();
+    out.push(DeviceInfo {
+      is_default: default_name.as_deref() == Some(name.as_str()),
+      name,
+      sample_rates,
+      channels,
+    });
+  }
+  Ok(out)
+}
+
+/// One entry of `supported_input_configs`: any rate in
+/// `min_sample_rate..=max_sample_rate` works with this channel count and
+/// sample format ("i16", "f32", ...).
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigRange {
+  pub channels: u16,
+  pub min_sample_rate: u32,
+  pub max_sample_rate: u32,
+  pub sample_format: String,
+}
+
+/// Everything a device can capture, for the advanced `RecordingConfig`
+/// picker. `default` is what the recorder uses when given no config.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceCapabilities {
+  pub name: String,
+  pub configs: Vec<ConfigRange>,
+  pub default: Option<ConfigRange>,
+}
+
+/// Lists the supported input configs of the device named `name` (the default
+/// device when `None`). Errors if the device has gone away.
+pub fn device_capabilities(name: Option<&str>) -> Result<DeviceCapabilities> {
+  let host = cpal::default_host();
+  let device = find_input_device(&host, name)?;
+  let configs = device
+    .supported_input_configs()?
+    .map(|range| ConfigRange {
+      channels: range.channels(),
+      min_sample_rate: range.min_sample_rate().0,
+      max_sample_rate: range.max_sample_rate().0,
+      sample_format: range.sample_format().to_string(),
+    })
+    .collect();
+  let default = device.default_input_config().ok().map(|c| ConfigRange {
+    channels: c.channels(),
+    min_sample_rate: c.sample_rate().0,
+    max_sample_rate: c.sample_rate().0,
+    sample_format: c.sample_format().to_string(),
+  });
+  Ok(DeviceCapabilities { name: device.name()?, configs, default })
+}
+
+/// Resolves an input device by exact name, or the host default when `name`
+/// is `None`. A named device that isn't present is an error; we never fall
+/// back to the default behind the caller's back.
+pub fn find_input_device(host: &cpal::Host, name: Option<&str>) -> Result<cpal::Device> {
+  match name {
+    Some(wanted) => host
+      .input_devices()?
+      .find(|d| d.name().map(|n| n == wanted).unwrap_or(false))
+      .ok_or_else(|| anyhow!("input device '{wanted}' not found")),
+    None => host
+      .default_input_device()
+      .ok_or_else(|| anyhow!("no input device available")),
+  }
+}
+
+/// Whether the device `find_input_device` would resolve `name` to is still
+/// there. A failing backend counts as gone.
+pub fn input_device_present(host: &cpal::Host, name: Option<&str>) -> bool {
+  match name {
+    Some(wanted) => host
+      .input_devices()
+      .is_ok_and(|mut ds| ds.any(|d| d.name().is_ok_and(|n| n == wanted))),
+    None => host.default_input_device().is_some(),
+  }
+}
+
+/// The device whose playback is recorded for system audio. cpal only offers
+/// loopback through WASAPI on Windows, where an input stream opened on an
+/// output device captures what it plays; that is the default output here.
+/// Elsewhere a virtual device (BlackHole on macOS, a "Monitor of ..." source
+/// on PulseAudio/PipeWire) can be picked as the input device instead.
+pub fn find_loopback_device(host: &cpal::Host) -> Result<cpal::Device> {
+  if !cfg!(windows) {
+    return Err(anyhow!(
+      "system audio capture is only supported on Windows; select a loopback device as the \
+       microphone instead"
+    ));
+  }
+  host
+    .default_output_device()
+    .ok_or_else(|| anyhow!("no output device to capture system audio from"))
+}
+
+// Cheap fingerprint of the device list: the default's name and all input
+// names, without probing each device's configs.
+fn device_snapshot(host: &cpal::Host) -> (Option<String>, Vec<String>) {
+  let default = host.default_input_device().and_then(|d| d.name().ok());
+  let mut names: Vec<String> = host
+    .input_devices()
+    .map(|ds| ds.filter_map(|d| d.name().ok()).collect())
+    .unwrap_or_default();
+  names.sort_unstable();
+  (default, names)
+}
+
+/// Polls the input devices in the background and emits `devices://changed`
+/// with the fresh `list_input_devices` result whenever a device appears,
+/// disappears or the default changes.
+pub fn spawn_device_watcher(app: AppHandle) -> std::io::Result<()> {
+  thread::Builder::new().name("device-watcher".into()).spawn(move || {
+    let host = cpal::default_host();
+    let mut last = device_snapshot(&host);
+    loop {
+      thread::sleep(WATCH_INTERVAL);
+      let now = device_snapshot(&host);
+      if now == last {
+        continue;
+      }
+      last = now;
+      match list_input_devices() {
+        Ok(devices) => {
+          app.emit(DEVICES_CHANGED_EVENT, devices).ok();
+        }
+        Err(e) => tracing::warn!("listing devices after a change failed: {e}"),
+      }
+    }
+  })?;
+  Ok(())
+}
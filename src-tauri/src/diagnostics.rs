@@ -0,0 +1,57 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde::Serialize;
+use std::fs;
+
+use crate::recorder::storage_dir;
+use crate::storage::free_space;
+use crate::transcribe::resolve_model_path;
+
+/// Result of `diagnostics_cmd`: the environment checks a support request
+/// usually needs, in one place. Nothing secret goes in here; the API key is
+/// only reported as present or not.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostics {
+  pub app_version: String,
+  pub os: String,
+  pub arch: String,
+  pub audio_host: String,
+  pub default_input_device: Option<String>,
+  pub storage_dir: String,
+  pub storage_writable: bool,
+  // None when the volume couldn't be queried.
+  pub free_disk_bytes: Option<u64>,
+  pub whisper_model_path: String,
+  pub whisper_model_present: bool,
+  pub api_key_configured: bool,
+}
+
+/// Gathers the report. Every check is best effort: a failing probe shows up
+/// as a false/None field rather than an error.
+pub fn collect(api_key_configured: bool) -> Diagnostics {
+  let host = cpal::default_host();
+  let dir = storage_dir();
+  let model = resolve_model_path(None);
+  Diagnostics {
+    app_version: env!("CARGO_PKG_VERSION").to_string(),
+    os: std::env::consts::OS.to_string(),
+    arch: std::env::consts::ARCH.to_string(),
+    audio_host: host.id().name().to_string(),
+    default_input_device: host.default_input_device().and_then(|d| d.name().ok()),
+    storage_dir: dir.to_string_lossy().to_string(),
+    storage_writable: is_writable(&dir),
+    free_disk_bytes: free_space(&dir).ok(),
+    whisper_model_path: model.to_string_lossy().to_string(),
+    whisper_model_present: model.is_file(),
+    api_key_configured,
+  }
+}
+
+// Same probe as validate_storage_dir, but creating the folder first since
+// the default one may not exist before the first recording.
+fn is_writable(dir: &std::path::Path) -> bool {
+  let probe = dir.join(".applesauce-write-test");
+  fs::create_dir_all(dir)
+    .and_then(|_| fs::write(&probe, b"ok"))
+    .and_then(|_| fs::remove_file(&probe))
+    .is_ok()
+}
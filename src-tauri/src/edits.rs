@@ -0,0 +1,258 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+use crate::audio::{convert_format, read_wav_f32, write_wav_f32};
+use crate::sessions::{
+  find_recording, read_meta, reserve_stem, sanitize_name, session_summary, validate_session_id,
+  write_meta, Marker, SessionMeta, SessionSummary,
+};
+
+pub const DEFAULT_SILENCE_DB: f64 = -45.0;
+// Silence is judged on windows this long, and this much is kept on either
+// side of the first/last sound so word onsets aren't clipped.
+const WINDOW_SECS: f64 = 0.01;
+const PAD_SECS: f64 = 0.1;
+
+/// Result of `trim_silence_cmd`. `session_id` is the copy's id, or the
+/// original's when trimmed in place.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrimReport {
+  pub session_id: String,
+  pub path: String,
+  pub removed_start_secs: f64,
+  pub removed_end_secs: f64,
+  pub duration_secs: f64,
+}
+
+// The session's WAV; the editing helpers don't decode compressed formats.
+fn session_wav(dir: &Path, session_id: &str) -> Result<PathBuf> {
+  let path = find_recording(dir, session_id)
+    .ok_or_else(|| anyhow!("no recording found for session '{session_id}'"))?;
+  if !path.extension().is_some_and(|e| e.eq_ignore_ascii_case("wav")) {
+    return Err(anyhow!("only WAV recordings can be edited"));
+  }
+  Ok(path)
+}
+
+/// Cuts silence (windows below `threshold_db` dBFS) off both ends of a
+/// recording. Interior pauses are left alone. Writes `<id>_trimmed` as a
+/// new session unless `in_place`.
+pub fn trim_silence(
+  dir: &Path,
+  session_id: &str,
+  threshold_db: f64,
+  in_place: bool,
+) -> Result<TrimReport> {
+  let path = session_wav(dir, session_id)?;
+  let (spec, samples) = read_wav_f32(&path)?;
+  let channels = spec.channels.max(1) as usize;
+  let rate = spec.sample_rate.max(1) as f64;
+  let frames = samples.len() / channels;
+  let window = ((rate * WINDOW_SECS) as usize).max(1);
+  let threshold = 10f64.powf(threshold_db / 20.0);
+
+  let loud = |w: usize| {
+    let chunk = &samples[w * window * channels..((w + 1) * window).min(frames) * channels];
+    let sum_sq: f64 = chunk.iter().map(|&s| (s as f64) * (s as f64)).sum();
+    (sum_sq / chunk.len().max(1) as f64).sqrt() >= threshold
+  };
+  let windows = frames.div_ceil(window);
+  let first = (0..windows)
+    .find(|&w| loud(w))
+    .ok_or_else(|| anyhow!("the recording is silent at {threshold_db} dB; nothing to keep"))?;
+  let last = (0..windows).rev().find(|&w| loud(w)).unwrap_or(first);
+
+  let pad = (rate * PAD_SECS) as usize;
+  let start = (first * window).saturating_sub(pad);
+  let end = ((last + 1) * window + pad).min(frames);
+  let kept = &samples[start * channels..end * channels];
+
+  let reserved = match in_place {
+    true => None,
+    false => Some(reserve_stem(dir, &format!("{session_id}_trimmed"))?),
+  };
+  let out_id = reserved.as_ref().map_or_else(|| session_id.to_string(), |r| r.id.clone());
+  let out = dir.join(format!("{out_id}.wav"));
+  // Written beside and swapped in, so a failure leaves the original intact.
+  let tmp = out.with_extension("wav.tmp");
+  write_wav_f32(&tmp, spec, kept)?;
+  fs::rename(&tmp, &out)?;
+
+  let duration_secs = (end - start) as f64 / rate;
+  if let Some(mut meta) = read_meta(dir, session_id) {
+    // A trimmed copy is a new session, not the original or its source file.
+    if !in_place {
+      meta.uid = None;
+      meta.source_hash = None;
+    }
+    meta.session_id = out_id.clone();
+    meta.duration_secs = duration_secs;
+    // Markers keep pointing at the same audio; ones in the cut-off parts go.
+    let offset = start as f64 / rate;
+    meta.markers.retain_mut(|m| {
+      m.secs -= offset;
+      (0.0..=duration_secs).contains(&m.secs)
+    });
+    write_meta(dir, &meta)?;
+  }
+  Ok(TrimReport {
+    session_id: out_id,
+    path: out.to_string_lossy().to_string(),
+    removed_start_secs: start as f64 / rate,
+    removed_end_secs: (frames - end) as f64 / rate,
+    duration_secs,
+  })
+}
+
+/// Joins several WAV sessions end to end into a new one, in the given order.
+/// The first session sets the format; others are converted to it when
+/// `allow_resample`, and rejected otherwise. Markers carry over, shifted to
+/// where their part starts, and each join counts as a pause.
+pub fn merge_sessions(
+  dir: &Path,
+  session_ids: &[String],
+  output_name: Option<&str>,
+  allow_resample: bool,
+) -> Result<SessionSummary> {
+  if session_ids.len() < 2 {
+    return Err(anyhow!("pick at least two sessions to merge"));
+  }
+  let mut spec = None;
+  let mut merged: Vec<f32> = Vec::new();
+  let mut markers: Vec<Marker> = Vec::new();
+  let mut paused_segments = session_ids.len() as u32 - 1;
+  let mut first_meta = None;
+  let mut tags: Vec<String> = Vec::new();
+  for id in session_ids {
+    validate_session_id(id)?;
+    let (part_spec, samples) = read_wav_f32(&session_wav(dir, id)?)?;
+    let target = *spec.get_or_insert(part_spec);
+    let samples =
+      if (part_spec.sample_rate, part_spec.channels) == (target.sample_rate, target.channels) {
+        samples
+      } else if allow_resample {
+        convert_format(&samples, &part_spec, &target)
+      } else {
+        return Err(anyhow!(
+          "'{id}' is {} Hz / {} ch but '{}' is {} Hz / {} ch; allow resampling to merge them",
+          part_spec.sample_rate,
+          part_spec.channels,
+          session_ids[0],
+          target.sample_rate,
+          target.channels
+        ));
+      };
+    let offset =
+      merged.len() as f64 / target.channels.max(1) as f64 / target.sample_rate.max(1) as f64;
+    let meta = read_meta(dir, id);
+    if let Some(meta) = &meta {
+      paused_segments += meta.paused_segments;
+      markers.extend(meta.markers.iter().map(|m| Marker {
+        secs: m.secs + offset,
+        label: m.label.clone(),
+      }));
+      for tag in &meta.tags {
+        if !tags.contains(tag) {
+          tags.push(tag.clone());
+        }
+      }
+    }
+    if first_meta.is_none() {
+      first_meta = Some(meta);
+    }
+    merged.extend(samples);
+  }
+  let spec = spec.expect("at least two sessions were read");
+
+  let base = match output_name {
+    Some(name) => sanitize_name(name)?,
+    None => format!("{}_merged", session_ids[0]),
+  };
+  let reserved = reserve_stem(dir, &base)?;
+  let out_id = reserved.id.clone();
+  let out = dir.join(format!("{out_id}.wav"));
+  let tmp = out.with_extension("wav.tmp");
+  write_wav_f32(&tmp, spec, &merged)?;
+  fs::rename(&tmp, &out)?;
+
+  let first_meta = first_meta.flatten();
+  let new = SessionMeta::new(&out_id, spec.sample_rate, spec.channels);
+  let meta = SessionMeta {
+    started_at: first_meta.as_ref().map_or(new.started_at.clone(), |m| m.started_at.clone()),
+    duration_secs: merged.len() as f64
+      / spec.channels.max(1) as f64
+      / spec.sample_rate.max(1) as f64,
+    device_name: first_meta.and_then(|m| m.device_name),
+    paused_segments,
+    markers,
+    tags,
+    ..new
+  };
+  write_meta(dir, &meta)?;
+  session_summary(dir, &out_id)
+}
+
+/// Cuts a WAV session in two at `at_secs` (rounded to the nearest frame),
+/// writing `<id>_part1` and `<id>_part2` as new sessions and leaving the
+/// original alone. Markers go to the part they fall in, re-timed to it.
+pub fn split_session(dir: &Path, session_id: &str, at_secs: f64) -> Result<Vec<SessionSummary>> {
+  validate_session_id(session_id)?;
+  let (spec, samples) = read_wav_f32(&session_wav(dir, session_id)?)?;
+  let channels = spec.channels.max(1) as usize;
+  let rate = spec.sample_rate.max(1) as f64;
+  let frames = samples.len() / channels;
+  let duration = frames as f64 / rate;
+  let at = (at_secs * rate).round();
+  if !at_secs.is_finite() || at <= 0.0 || at >= frames as f64 {
+    return Err(anyhow!("split point {at_secs}s is outside the recording (0-{duration:.2}s)"));
+  }
+  let at = at as usize;
+  let split_secs = at as f64 / rate;
+
+  let meta = read_meta(dir, session_id);
+  let started = meta
+    .as_ref()
+    .and_then(|m| chrono::DateTime::parse_from_rfc3339(&m.started_at).ok());
+  let parts = [
+    (&samples[..at * channels], 0.0, split_secs),
+    (&samples[at * channels..frames * channels], split_secs, duration),
+  ];
+  let mut out = Vec::new();
+  for (n, (part, from, to)) in parts.into_iter().enumerate() {
+    let reserved = reserve_stem(dir, &format!("{session_id}_part{}", n + 1))?;
+    let part_id = reserved.id.clone();
+    let path = dir.join(format!("{part_id}.wav"));
+    let tmp = path.with_extension("wav.tmp");
+    write_wav_f32(&tmp, spec, part)?;
+    fs::rename(&tmp, &path)?;
+
+    let markers = meta.as_ref().map_or(Vec::new(), |m| {
+      m.markers
+        .iter()
+        .filter(|mk| mk.secs >= from && mk.secs < to)
+        .map(|mk| Marker { secs: mk.secs - from, label: mk.label.clone() })
+        .collect()
+    });
+    let started_at = match started {
+      Some(t) => (t + chrono::Duration::milliseconds((from * 1000.0) as i64)).to_rfc3339(),
+      None => chrono::Local::now().to_rfc3339(),
+    };
+    let part_meta = SessionMeta {
+      started_at,
+      duration_secs: to - from,
+      device_name: meta.as_ref().and_then(|m| m.device_name.clone()),
+      markers,
+      // Not re-measured per part; the whole recording's figure is close.
+      clipping_pct: meta.as_ref().and_then(|m| m.clipping_pct),
+      tags: meta.as_ref().map_or(Vec::new(), |m| m.tags.clone()),
+      ..SessionMeta::new(&part_id, spec.sample_rate, spec.channels)
+    };
+    write_meta(dir, &part_meta)?;
+    out.push(session_summary(dir, &part_id)?);
+  }
+  Ok(out)
+}
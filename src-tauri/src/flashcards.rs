@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+use crate::notes::notes_path;
+use crate::openai;
+use crate::transcribe::read_transcript;
+
+// Longest "term" the heuristic accepts, in words; longer subjects are
+// usually whole clauses rather than vocabulary.
+const MAX_TERM_WORDS: usize = 6;
+// Phrases that mark a definition: "<term> is defined as <definition>".
+const DEFINITION_MARKERS: [&str; 7] = [
+  " is defined as ",
+  " refers to ",
+  " is known as ",
+  " means ",
+  " is a ",
+  " is an ",
+  " are ",
+];
+
+const CARDS_SYSTEM_PROMPT: &str = "You turn lecture material into study flashcards. \
+  Reply with only a JSON array of objects with \"term\" and \"definition\" string fields. \
+  Keep terms short and definitions to one or two sentences.";
+
+/// One term/definition (or question/answer) pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Card {
+  pub term: String,
+  pub definition: String,
+}
+
+/// The study text for a session: generated notes if there are any,
+/// otherwise the transcript.
+pub fn source_text(dir: &Path, session_id: &str) -> Result<String> {
+  if let Ok(text) = fs::read_to_string(notes_path(dir, session_id)) {
+    return Ok(text);
+  }
+  Ok(read_transcript(dir, session_id)?.text)
+}
+
+/// Cards for a session. With an API key the LLM writes them (steered by the
+/// user's prompt preset); without one a definition-sentence heuristic is used.
+pub fn make_cards(
+  dir: &Path,
+  session_id: &str,
+  api_key: Option<&str>,
+  prompt_preset: Option<&str>,
+) -> Result<Vec<Card>> {
+  let text = source_text(dir, session_id)?;
+  let cards = match api_key {
+    Some(key) => llm_cards(key, &text, prompt_preset)?,
+    None => heuristic_cards(&text),
+  };
+  if cards.is_empty() {
+    return Err(anyhow!("couldn't find any terms to make flashcards from"));
+  }
+  Ok(cards)
+}
+
+fn llm_cards(api_key: &str, text: &str, prompt_preset: Option<&str>) -> Result<Vec<Card>> {
+  let system = match prompt_preset {
+    Some(extra) if !extra.trim().is_empty() => format!("{CARDS_SYSTEM_PROMPT}\n\n{extra}"),
+    _ => CARDS_SYSTEM_PROMPT.to_string(),
+  };
+  let reply = openai::chat(api_key, &system, text)?;
+  // Models sometimes wrap the JSON in a ```json fence.
+  let json = reply
+    .trim()
+    .trim_start_matches("```json")
+    .trim_start_matches("```")
+    .trim_end_matches("```");
+  serde_json::from_str(json).map_err(|e| anyhow!("couldn't read flashcards from the reply: {e}"))
+}
+
+/// Picks sentences shaped like "X is a Y" / "X refers to Y".
+pub fn heuristic_cards(text: &str) -> Vec<Card> {
+  let mut cards = Vec::new();
+  for sentence in text.split(['.', '?', '!', '\n']) {
+    let sentence = sentence.trim();
+    let lower = sentence.to_lowercase();
+    let Some((at, marker)) = DEFINITION_MARKERS
+      .iter()
+      .filter_map(|m| lower.find(m).map(|at| (at, *m)))
+      .min_by_key(|(at, _)| *at)
+    else {
+      continue;
+    };
+    // Lowercasing can shift byte offsets for some scripts; skip those.
+    let (Some(term), Some(definition)) =
+      (sentence.get(..at), sentence.get(at + marker.len()..))
+    else {
+      continue;
+    };
+    let (term, definition) = (term.trim(), definition.trim());
+    let words = term.split_whitespace().count();
+    if words == 0 || words > MAX_TERM_WORDS || definition.is_empty() {
+      continue;
+    }
+    cards.push(Card {
+      term: term.to_string(),
+      definition: definition.to_string(),
+    });
+  }
+  cards
+}
+
+// Quizlet splits on tabs and newlines, so neither may appear in a field.
+fn tsv_field(s: &str) -> String {
+  s.split(['\t', '\n', '\r'])
+    .map(str::trim)
+    .filter(|p| !p.is_empty())
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Writes `<session_id>.quizlet.tsv`: one `term<TAB>definition` per line,
+/// the format Quizlet's "Import" box accepts.
+pub fn write_quizlet_tsv(dir: &Path, session_id: &str, cards: &[Card]) -> Result<PathBuf> {
+  let body: String = cards
+    .iter()
+    .map(|c| format!("{}\t{}\n", tsv_field(&c.term), tsv_field(&c.definition)))
+    .collect();
+  let path = dir.join(format!("{session_id}.quizlet.tsv"));
+  fs::write(&path, body)?;
+  Ok(path)
+}
@@ -0,0 +1,374 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{
+  ffi::OsStr,
+  fs::{self, File},
+  io::Read,
+  path::Path,
+};
+
+use crate::audio::{repair_wav_header, validate_wav, WHISPER_RATE};
+use crate::recorder::{new_session_id, sweep_temp_recordings};
+use crate::sessions::{
+  is_recording, is_reserved, meta_path, read_meta, reserve_stem, session_summary,
+  validate_session_id, write_meta, SessionMeta, SessionSummary,
+};
+use crate::tools::{require_tool, run_tool};
+use crate::trash::trash_session;
+
+pub const SUPPORTED_AUDIO: [&str; 5] = ["wav", "mp3", "m4a", "flac", "ogg"];
+pub const SUPPORTED_VIDEO: [&str; 5] = ["mp4", "mov", "mkv", "webm", "avi"];
+pub const DEFAULT_MAX_IMPORT_MB: u64 = 500;
+
+fn has_extension(path: &Path, allowed: &[&str]) -> bool {
+  path
+    .extension()
+    .and_then(|e| e.to_str())
+    .is_some_and(|e| allowed.iter().any(|a| e.eq_ignore_ascii_case(a)))
+}
+
+// Already what transcription wants, so it can be copied as-is.
+fn is_whisper_ready_wav(path: &Path) -> bool {
+  hound::WavReader::open(path).is_ok_and(|r| {
+    let spec = r.spec();
+    spec.sample_rate == WHISPER_RATE
+      && spec.channels == 1
+      && spec.bits_per_sample == 16
+      && spec.sample_format == hound::SampleFormat::Int
+  })
+}
+
+/// Converts any ffmpeg-readable input (audio, or a video's audio track) to
+/// 16 kHz mono 16-bit WAV.
+pub fn convert_to_wav(src: &Path, dest: &Path) -> Result<()> {
+  let ffmpeg = require_tool("ffmpeg", "install ffmpeg to import compressed audio")?;
+  let rate = WHISPER_RATE.to_string();
+  let args: Vec<&OsStr> = ["-hide_banner", "-loglevel", "error", "-y", "-i"]
+    .map(OsStr::new)
+    .into_iter()
+    .chain([src.as_os_str()])
+    .chain(["-vn", "-ac", "1", "-ar", &rate, "-c:a", "pcm_s16le"].map(OsStr::new))
+    .chain([dest.as_os_str()])
+    .collect();
+  run_tool(&ffmpeg, args)?;
+  Ok(())
+}
+
+/// What import_audio did. With `duplicate` set nothing was imported and
+/// `session` is the existing session with the same content.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedAudio {
+  pub session: SessionSummary,
+  pub duplicate: bool,
+}
+
+/// Copies (or converts) an audio file into `dir` as a new session and
+/// writes its metadata. Files over `max_mb` are refused. A file already in
+/// the library (same SHA-1 as an earlier import's source, or byte-identical
+/// to a recording) is not imported again unless `force` is set.
+pub fn import_audio(src: &Path, dir: &Path, max_mb: u64, force: bool) -> Result<ImportedAudio> {
+  if !src.is_file() {
+    return Err(anyhow!("file not found: {}", src.display()));
+  }
+  if !has_extension(src, &SUPPORTED_AUDIO) {
+    return Err(anyhow!(
+      "unsupported audio format: {} (expected {})",
+      src.display(),
+      SUPPORTED_AUDIO.join("/")
+    ));
+  }
+  let size = fs::metadata(src)?.len();
+  if size > max_mb * 1024 * 1024 {
+    return Err(anyhow!(
+      "{} is {:.0} MB, over the {max_mb} MB import limit",
+      src.display(),
+      size as f64 / (1024.0 * 1024.0)
+    ));
+  }
+
+  let hash = file_sha1(src)?;
+  if !force {
+    if let Some(existing) = find_duplicate(dir, &hash, size) {
+      tracing::info!("{} is already imported as {existing}", src.display());
+      let session = session_summary(dir, &existing)?;
+      return Ok(ImportedAudio { session, duplicate: true });
+    }
+  }
+  let session = new_session_from(src, dir, Some(hash))?;
+  Ok(ImportedAudio { session, duplicate: false })
+}
+
+/// Imports one file of either kind, picking by extension. An audio file
+/// that's already in the library gives back the existing session.
+pub fn import_any(src: &Path, dir: &Path, max_mb: u64) -> Result<SessionSummary> {
+  if has_extension(src, &SUPPORTED_VIDEO) {
+    import_video(src, dir)
+  } else {
+    import_audio(src, dir, max_mb, false).map(|i| i.session)
+  }
+}
+
+fn file_sha1(path: &Path) -> Result<String> {
+  let mut file = File::open(path)?;
+  let mut hasher = sha1_smol::Sha1::new();
+  let mut buf = vec![0u8; 64 * 1024];
+  loop {
+    let n = file.read(&mut buf)?;
+    if n == 0 {
+      break;
+    }
+    hasher.update(&buf[..n]);
+  }
+  Ok(hasher.digest().to_string())
+}
+
+// A session imported from the same content, or whose recording is the same
+// bytes (re-importing one of our own WAVs). Only recordings of the same size
+// get hashed, so this stays cheap.
+fn find_duplicate(dir: &Path, hash: &str, size: u64) -> Option<String> {
+  fs::read_dir(dir).ok()?.flatten().find_map(|entry| {
+    let path = entry.path();
+    if !is_recording(&path) {
+      return None;
+    }
+    let id = path.file_stem()?.to_str()?;
+    let meta = read_meta(dir, id);
+    let same_source = meta.as_ref().and_then(|m| m.source_hash.as_deref()) == Some(hash);
+    let same_bytes = || {
+      entry.metadata().is_ok_and(|m| m.len() == size)
+        && file_sha1(&path).is_ok_and(|h| h == hash)
+    };
+    (same_source || same_bytes()).then(|| id.to_string())
+  })
+}
+
+/// Extracts the audio track of a video into `dir` as a new session. The
+/// summary's `duration_secs` is the extracted length.
+pub fn import_video(src: &Path, dir: &Path) -> Result<SessionSummary> {
+  if !src.is_file() {
+    return Err(anyhow!("file not found: {}", src.display()));
+  }
+  if !has_extension(src, &SUPPORTED_VIDEO) {
+    return Err(anyhow!(
+      "unsupported video format: {} (expected {})",
+      src.display(),
+      SUPPORTED_VIDEO.join("/")
+    ));
+  }
+  if !has_audio_stream(src)? {
+    return Err(anyhow!("{} has no audio track to import", src.display()));
+  }
+  new_session_from(src, dir, None)
+}
+
+/// Header-level facts about a file, for previewing an import before it's
+/// made. Nothing is decoded: WAVs are read by hound, anything else asked of
+/// ffprobe. `bits_per_sample` is None for lossy formats, which have none.
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioInfo {
+  pub format: String,
+  pub sample_rate: u32,
+  pub channels: u16,
+  pub bits_per_sample: Option<u16>,
+  pub duration_secs: f64,
+  pub size_bytes: u64,
+}
+
+/// Errors start with `[not-found]`, `[unsupported]` (not a format imports
+/// take) or `[corrupt]` (no readable audio in it).
+pub fn audio_info(path: &Path) -> Result<AudioInfo> {
+  if !path.is_file() {
+    return Err(anyhow!("[not-found] file not found: {}", path.display()));
+  }
+  if !has_extension(path, &SUPPORTED_AUDIO) && !has_extension(path, &SUPPORTED_VIDEO) {
+    return Err(anyhow!(
+      "[unsupported] {} is not a supported audio or video file",
+      path.display()
+    ));
+  }
+  let size_bytes = fs::metadata(path)?.len();
+  let format = path
+    .extension()
+    .and_then(|e| e.to_str())
+    .unwrap_or_default()
+    .to_ascii_lowercase();
+  if format == "wav" {
+    let reader = hound::WavReader::open(path)
+      .map_err(|e| anyhow!("[corrupt] can't read {}: {e}", path.display()))?;
+    let spec = reader.spec();
+    return Ok(AudioInfo {
+      format,
+      sample_rate: spec.sample_rate,
+      channels: spec.channels,
+      bits_per_sample: Some(spec.bits_per_sample),
+      duration_secs: reader.duration() as f64 / spec.sample_rate.max(1) as f64,
+      size_bytes,
+    });
+  }
+
+  let ffprobe = require_tool("ffprobe", "install ffmpeg (it includes ffprobe) to read this format")?;
+  let args: Vec<&OsStr> = ["-v", "error", "-select_streams", "a:0", "-show_entries"]
+    .map(OsStr::new)
+    .into_iter()
+    .chain(["stream=sample_rate,channels,bits_per_raw_sample:format=duration"].map(OsStr::new))
+    .chain(["-of", "default=noprint_wrappers=1"].map(OsStr::new))
+    .chain([path.as_os_str()])
+    .collect();
+  let out = run_tool(&ffprobe, args).map_err(|e| anyhow!("[corrupt] {e}"))?;
+  // `key=value` lines; "N/A" for what the container doesn't say.
+  let out = String::from_utf8_lossy(&out);
+  let field = |key: &str| {
+    out
+      .lines()
+      .filter_map(|l| l.split_once('='))
+      .find(|(k, _)| *k == key)
+      .and_then(|(_, v)| v.trim().parse::<f64>().ok())
+  };
+  let Some(sample_rate) = field("sample_rate") else {
+    return Err(anyhow!("[corrupt] {} has no readable audio stream", path.display()));
+  };
+  Ok(AudioInfo {
+    format,
+    sample_rate: sample_rate as u32,
+    channels: field("channels").unwrap_or(0.0) as u16,
+    bits_per_sample: field("bits_per_raw_sample").map(|b| b as u16).filter(|&b| b > 0),
+    duration_secs: field("duration").unwrap_or(0.0),
+    size_bytes,
+  })
+}
+
+// ffprobe lists one line per audio stream; none means a silent video.
+fn has_audio_stream(src: &Path) -> Result<bool> {
+  let ffprobe = require_tool("ffprobe", "install ffmpeg (it includes ffprobe) to import video")?;
+  let args: Vec<&OsStr> = ["-v", "error", "-select_streams", "a", "-show_entries"]
+    .map(OsStr::new)
+    .into_iter()
+    .chain(["stream=index", "-of", "csv=p=0"].map(OsStr::new))
+    .chain([src.as_os_str()])
+    .collect();
+  let out = run_tool(&ffprobe, args)?;
+  Ok(!String::from_utf8_lossy(&out).trim().is_empty())
+}
+
+// Copies or converts `src` to `<dir>/<new id>.wav`, then writes its sidecar.
+fn new_session_from(src: &Path, dir: &Path, source_hash: Option<String>) -> Result<SessionSummary> {
+  fs::create_dir_all(dir)?;
+  // Held until the sidecar is written, so nothing else takes the id.
+  let reserved = reserve_stem(dir, &new_session_id())?;
+  let session_id = &reserved.id;
+  let wav = dir.join(format!("{session_id}.wav"));
+  let copied = if is_whisper_ready_wav(src) {
+    fs::copy(src, &wav).map(|_| ()).map_err(Into::into)
+  } else {
+    convert_to_wav(src, &wav)
+  };
+  if let Err(e) = copied {
+    fs::remove_file(&wav).ok();
+    return Err(e);
+  }
+  finish_import(dir, session_id, &wav, source_hash)
+}
+
+/// Writes the sidecar for audio the frontend saved straight into `dir`
+/// (save_audio_base64), so it lists like any other session. Only a WAV has
+/// a header to read here; other formats are listed with a zero length and
+/// format.
+pub fn register_saved_audio(dir: &Path, session_id: &str, path: &Path) -> Result<SessionSummary> {
+  if has_extension(path, &["wav"]) {
+    return finish_import(dir, session_id, path, None);
+  }
+  write_meta(dir, &SessionMeta::new(session_id, 0, 0))?;
+  session_summary(dir, session_id)
+}
+
+fn finish_import(
+  dir: &Path,
+  session_id: &str,
+  wav: &Path,
+  source_hash: Option<String>,
+) -> Result<SessionSummary> {
+  let reader = hound::WavReader::open(wav)?;
+  let spec = reader.spec();
+  let meta = SessionMeta {
+    duration_secs: reader.duration() as f64 / spec.sample_rate.max(1) as f64,
+    source_hash,
+    ..SessionMeta::new(session_id, spec.sample_rate, spec.channels)
+  };
+  write_meta(dir, &meta)?;
+  session_summary(dir, session_id)
+}
+
+/// What recover_sessions did. `recovered` are session ids that now have
+/// metadata; `discarded` are leftovers deleted or sessions moved to the
+/// trash; `unreadable` are WAVs that couldn't be salvaged and were left alone
+/// because discarding wasn't asked for.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RecoveryReport {
+  pub recovered: Vec<String>,
+  pub discarded: Vec<String>,
+  pub unreadable: Vec<String>,
+}
+
+/// Picks up after a crash: settles whatever is left in `.tmp` (see
+/// `sweep_temp_recordings`) and gives WAVs without a sidecar JSON metadata
+/// from their header, fixing up the header first if the writer never
+/// finished it. WAVs that still can't be read are reported, or trashed when
+/// `discard_unreadable` is set. Must not run while recording.
+pub fn recover_sessions(dir: &Path, discard_unreadable: bool) -> Result<RecoveryReport> {
+  let sweep = sweep_temp_recordings(dir)?;
+  let mut report = RecoveryReport {
+    recovered: sweep.recovered,
+    discarded: sweep.discarded,
+    unreadable: Vec::new(),
+  };
+  if !dir.is_dir() {
+    return Ok(report);
+  }
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+      continue;
+    };
+    if !path.is_file() || !has_extension(&path, &["wav"]) || meta_path(dir, id).exists() {
+      continue;
+    }
+    // An import or download still writing it; the sidecar comes at the end.
+    if is_reserved(dir, id) {
+      continue;
+    }
+    match recover_wav(dir, id, &path) {
+      Ok(()) => report.recovered.push(id.to_string()),
+      Err(e) if discard_unreadable => {
+        tracing::warn!("trashing unreadable recording {id}: {e}");
+        trash_session(dir, id)?;
+        report.discarded.push(id.to_string());
+      }
+      Err(e) => {
+        tracing::warn!("recording {id} is unreadable: {e}");
+        report.unreadable.push(id.to_string());
+      }
+    }
+  }
+  Ok(report)
+}
+
+fn recover_wav(dir: &Path, id: &str, wav: &Path) -> Result<()> {
+  validate_session_id(id)?;
+  if validate_wav(wav).is_err() {
+    repair_wav_header(wav)?;
+    validate_wav(wav)?;
+  }
+  let reader = hound::WavReader::open(wav)?;
+  let spec = reader.spec();
+  let duration_secs = reader.duration() as f64 / spec.sample_rate.max(1) as f64;
+  // The file was last written when recording stopped.
+  let ended = fs::metadata(wav)?.modified()?;
+  let started = chrono::DateTime::<chrono::Local>::from(ended)
+    - chrono::Duration::milliseconds((duration_secs * 1000.0) as i64);
+  let meta = SessionMeta {
+    started_at: started.to_rfc3339(),
+    duration_secs,
+    ..SessionMeta::new(id, spec.sample_rate, spec.channels)
+  };
+  write_meta(dir, &meta)
+}
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+use tracing_appender::{
+  non_blocking::WorkerGuard,
+  rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::storage::app_data_dir;
+
+// One file per day, `applesauce.YYYY-MM-DD.log`; older ones beyond this
+// many are deleted as new ones are started.
+const KEEP_LOG_FILES: usize = 14;
+
+/// Where the log files go, in the app data dir next to settings.json.
+pub fn logs_dir() -> PathBuf {
+  app_data_dir().join("logs")
+}
+
+/// Sends `tracing` output to a daily-rotated file in `logs_dir()` and to
+/// stderr. `RUST_LOG` overrides the default `info` level. The returned guard
+/// flushes the file on drop, so keep it alive for the life of the app.
+/// Logging is best effort: if the file can't be opened, only stderr is used.
+pub fn init() -> Option<WorkerGuard> {
+  let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+  let stderr = fmt::layer().with_writer(std::io::stderr);
+  let appender = RollingFileAppender::builder()
+    .rotation(Rotation::DAILY)
+    .filename_prefix("applesauce")
+    .filename_suffix("log")
+    .max_log_files(KEEP_LOG_FILES)
+    .build(logs_dir());
+  match appender {
+    Ok(appender) => {
+      let (writer, guard) = tracing_appender::non_blocking(appender);
+      let file = fmt::layer().with_writer(writer).with_ansi(false);
+      tracing_subscriber::registry().with(filter).with(stderr).with(file).init();
+      Some(guard)
+    }
+    Err(e) => {
+      tracing_subscriber::registry().with(filter).with(stderr).init();
+      tracing::warn!("can't write logs to {}: {e}", logs_dir().display());
+      None
+    }
+  }
+}
@@ -0,0 +1,155 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{f64::consts::PI, fs, path::Path};
+
+use crate::audio::{read_wav_f32, write_wav_f32};
+use crate::sessions::find_recording;
+
+pub const DEFAULT_TARGET_LUFS: f64 = -16.0;
+// Normalized peaks stay at or below this, so playback never clips.
+const PEAK_CEILING_DBFS: f64 = -1.0;
+
+/// Result of `normalize_session_cmd`. `limited` means clip protection held
+/// the gain below what the target needed.
+#[derive(Debug, Clone, Serialize)]
+pub struct NormalizeReport {
+  pub path: String,
+  pub measured_lufs: f64,
+  pub target_lufs: f64,
+  pub gain_db: f64,
+  pub limited: bool,
+}
+
+// Direct-form-I biquad.
+struct Biquad {
+  b: [f64; 3],
+  a: [f64; 2],
+  x: [f64; 2],
+  y: [f64; 2],
+}
+
+impl Biquad {
+  fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+    let n = |v: f64| v / a[0];
+    Self {
+      b: b.map(n),
+      a: [n(a[1]), n(a[2])],
+      x: [0.0; 2],
+      y: [0.0; 2],
+    }
+  }
+
+  fn process(&mut self, x: f64) -> f64 {
+    let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+      - self.a[0] * self.y[0]
+      - self.a[1] * self.y[1];
+    self.x = [x, self.x[0]];
+    self.y = [y, self.y[0]];
+    y
+  }
+}
+
+// The two-stage K-weighting filter from ITU-R BS.1770 (shelf, then
+// high-pass), with coefficients derived for any sample rate.
+fn k_weighting(rate: f64) -> (Biquad, Biquad) {
+  let (gain_db, q, fc) = (3.999_843_853_973_347, 0.707_175_236_955_419_6, 1_681.974_450_955_533);
+  let a = 10f64.powf(gain_db / 40.0);
+  let w0 = 2.0 * PI * fc / rate;
+  let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * q));
+  let shelf = Biquad::new(
+    [
+      a * ((a + 1.0) + (a - 1.0) * cos + 2.0 * a.sqrt() * alpha),
+      -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+      a * ((a + 1.0) + (a - 1.0) * cos - 2.0 * a.sqrt() * alpha),
+    ],
+    [
+      (a + 1.0) - (a - 1.0) * cos + 2.0 * a.sqrt() * alpha,
+      2.0 * ((a - 1.0) - (a + 1.0) * cos),
+      (a + 1.0) - (a - 1.0) * cos - 2.0 * a.sqrt() * alpha,
+    ],
+  );
+  let (q, fc) = (0.500_327_037_323_877_3, 38.135_470_876_024_44);
+  let w0 = 2.0 * PI * fc / rate;
+  let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * q));
+  let high_pass = Biquad::new(
+    [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+    [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+  );
+  (shelf, high_pass)
+}
+
+/// Integrated loudness (LUFS) of interleaved samples per BS.1770: 400 ms
+/// blocks with 75% overlap, absolute gate at -70 LUFS and relative gate
+/// 10 LU below. None for silence.
+pub fn integrated_lufs(samples: &[f32], sample_rate: u32, channels: u16) -> Option<f64> {
+  let channels = channels.max(1) as usize;
+  let frames = samples.len() / channels;
+  // Squared K-weighted samples, summed across channels (all weighted 1.0,
+  // which holds for mono and stereo).
+  let mut filters: Vec<_> = (0..channels).map(|_| k_weighting(sample_rate as f64)).collect();
+  let mut power = vec![0.0_f64; frames];
+  for (i, frame) in samples.chunks_exact(channels).enumerate() {
+    for (s, (shelf, hp)) in frame.iter().zip(filters.iter_mut()) {
+      let y = hp.process(shelf.process(*s as f64));
+      power[i] += y * y;
+    }
+  }
+
+  let block = (sample_rate as usize * 400 / 1000).max(1);
+  let step = (block / 4).max(1);
+  let loudness = |ms: f64| -0.691 + 10.0 * ms.log10();
+  let mut blocks = Vec::new();
+  let mut start = 0;
+  while start + block <= frames {
+    blocks.push(power[start..start + block].iter().sum::<f64>() / block as f64);
+    start += step;
+  }
+  let gated: Vec<f64> = blocks.into_iter().filter(|&ms| loudness(ms) > -70.0).collect();
+  if gated.is_empty() {
+    return None;
+  }
+  let relative = loudness(gated.iter().sum::<f64>() / gated.len() as f64) - 10.0;
+  let kept: Vec<f64> = gated.into_iter().filter(|&ms| loudness(ms) > relative).collect();
+  Some(loudness(kept.iter().sum::<f64>() / kept.len().max(1) as f64))
+}
+
+/// Measures a session's loudness and rewrites its WAV with the gain that
+/// reaches `target_lufs`, capped so peaks stay under -1 dBFS.
+pub fn normalize_session(
+  dir: &Path,
+  session_id: &str,
+  target_lufs: f64,
+) -> Result<NormalizeReport> {
+  if !(-40.0..=-5.0).contains(&target_lufs) {
+    return Err(anyhow!("target loudness must be between -40 and -5 LUFS"));
+  }
+  let path = find_recording(dir, session_id)
+    .ok_or_else(|| anyhow!("no recording found for session '{session_id}'"))?;
+  if !path.extension().is_some_and(|e| e.eq_ignore_ascii_case("wav")) {
+    return Err(anyhow!("only WAV recordings can be normalized"));
+  }
+  let (spec, mut samples) = read_wav_f32(&path)?;
+  let measured = integrated_lufs(&samples, spec.sample_rate, spec.channels)
+    .ok_or_else(|| anyhow!("recording is silent; nothing to normalize"))?;
+
+  let peak = samples.iter().fold(0.0_f32, |m, s| m.max(s.abs())) as f64;
+  let wanted = target_lufs - measured;
+  let headroom = PEAK_CEILING_DBFS - 20.0 * peak.log10();
+  let gain_db = wanted.min(headroom);
+  let factor = 10f64.powf(gain_db / 20.0) as f32;
+  for s in &mut samples {
+    *s *= factor;
+  }
+
+  // Write beside the original and swap, so a failure leaves it intact.
+  let tmp = path.with_extension("wav.tmp");
+  write_wav_f32(&tmp, spec, &samples)?;
+  fs::rename(&tmp, &path)?;
+  Ok(NormalizeReport {
+    path: path.to_string_lossy().to_string(),
+    measured_lufs: measured,
+    target_lufs,
+    gain_db,
+    limited: headroom < wanted,
+  })
+}
@@ -1,166 +1,862 @@
 // src-tauri/src/main.rs
 // Tauri v2 command surface for Applesauce.
-// - Recording commands backed by recorder.rs (cpal + hound).
-// - Stubs for imports/transcribe/storage/API key/prompt/quizlet.
-// - save_audio_base64 persists audio blobs from the web UI into Downloads/ApplesauceCache.
+// - Recording commands backed by recorder.rs (cpal + hound), device listing in devices.rs.
+// - Local transcription via whisper.cpp (transcribe.rs); stubs for imports/API key/prompt/quizlet.
+// - save_audio_base64 persists audio blobs from the web UI as sessions in storage_dir().
 
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod anki;
+mod audio;
+mod bundle;
+mod devices;
+mod diagnostics;
+mod edits;
+mod flashcards;
+mod imports;
+mod logging;
+mod loudness;
+mod notes;
+mod openai;
+mod pdf;
+mod peaks;
+mod playback;
 mod recorder;
+mod search;
+mod secrets;
+mod sessions;
+mod settings;
+mod storage;
+mod subtitles;
+mod tools;
+mod transcribe;
+mod trash;
+mod youtube;
 
+use audio::validate_wav;
+use bundle::{export_session_zip, import_session_zip};
+use devices::{
+  device_capabilities, list_input_devices, spawn_device_watcher, DeviceCapabilities, DeviceInfo,
+};
+use edits::{merge_sessions, split_session, trim_silence, TrimReport, DEFAULT_SILENCE_DB};
+use imports::{
+  audio_info, convert_to_wav, import_any, import_audio, import_video, recover_sessions,
+  register_saved_audio, AudioInfo, ImportedAudio, RecoveryReport, DEFAULT_MAX_IMPORT_MB,
+};
+use loudness::{normalize_session, NormalizeReport, DEFAULT_TARGET_LUFS};
+use pdf::{import_pdf, PdfImport};
+use peaks::{waveform_peaks, DEFAULT_BUCKETS};
+use playback::{
+  pause_playback, play_session, resume_playback, seek_playback, stop_playback, PlaybackStarted,
+  PlayerState,
+};
+use search::{search_transcripts, TranscriptMatch};
+use sessions::{
+  cleanup_old_sessions, find_recording, list_sessions_page, rename_session, reserve_stem,
+  search_sessions, set_session_tags, validate_session_id, SessionPage, SessionSummary,
+  RECORDING_EXTENSIONS,
+};
+use settings::{load_settings, update_settings, Settings};
+use storage::{
+  move_dir_contents, reveal_path, storage_usage, validate_storage_dir, ClearSummary, MoveReport,
+  StorageUsage,
+};
+use subtitles::{export_transcript, SubtitleFormat};
+use transcribe::{
+  model_status, parse_language, resolve_model_path, resolve_session_wav, transcribe_wav,
+  transcript_path, write_transcript, Backend, CancelToken, ModelStatus, Progress, Segment,
+  Transcript, PROGRESS_EVENT, SEGMENT_EVENT,
+};
 use recorder::{
-  pause_recording, resume_recording, start_recording, stop_recording, storage_dir, RecorderState,
+  add_marker, begin_mic_test, cancel_recording, default_storage_dir, emit_state, new_session_id,
+  pause_recording, recording_status, resume_recording, resume_session, set_input_gain,
+  start_recording, stop_recording, storage_dir, test_microphone, MicTestReport, RecorderState,
+  RecorderStatus, RecordingConfig, StartedRecording, StoppedRecording,
 };
 
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
-use tauri::{Manager, State}; // Manager needed for app_handle.path()
+// parking_lot's Mutex doesn't poison: a command that panics while holding
+// the recorder lock doesn't take every later recording command down with it.
+use parking_lot::Mutex;
+use tauri::{Emitter, Manager, State};
 
 // For save_audio_base64
-use std::{fs, path::PathBuf};
-use base64::{engine::general_purpose, Engine as _};
-use chrono::Local;
+use std::{
+  fs,
+  io::{self, BufWriter, Write},
+  path::Path,
+};
+use base64::{engine::general_purpose, read::DecoderReader};
 
 struct SharedState {
   // Wrap RecorderState in a Mutex so multiple commands can access it safely.
   recorder: Mutex<RecorderState>,
+  // Session playback (playback.rs); one at a time.
+  player: Mutex<PlayerState>,
+  // Flipped by cancel_transcription_cmd; the transcription loop polls it.
+  transcribe_cancel: CancelToken,
 }
 
 #[derive(Serialize)]
 struct StartResponse {
   session_id: String,
   first_chunk: String, // keep shape consistent; not actually used yet
+  // Format the device actually opened with (may differ from the request).
+  config: Option<RecordingConfig>,
+  // True when a recording was already running and this is that session.
+  already_running: bool,
 }
 
 #[derive(Serialize)]
 struct StopResponse {
   message: String,
-  final_wav: Option<String>,
+  // `<id>.wav`, `.mp3` or `.flac`, per `config.format`.
+  final_path: Option<String>,
+  device_name: Option<String>,
+  // Recorded (unpaused) length, from the samples actually written.
+  duration_secs: f64,
+  // Percent of samples at or near full scale; see recording://clipping-warning.
+  clipping_pct: f32,
 }
 
 /* --------------------------- Recording commands --------------------------- */
 
+#[derive(Deserialize, Default)]
+struct StartRecordingArgs {
+  // Exact name from list_input_devices_cmd; None = system default.
+  device_name: Option<String>,
+  // None = whatever the device defaults to.
+  config: Option<RecordingConfig>,
+  // Names the file, e.g. "Biology Lecture 3" -> Biology_Lecture_3.wav; a
+  // counter is added if taken. None = the generated id.
+  label: Option<String>,
+}
+
 #[tauri::command]
-fn start_recording_cmd(state: State<SharedState>) -> Result<StartResponse, String> {
-  let mut lock = state.recorder.lock().unwrap();
-  start_recording(&mut *lock)
-    .map(|(sid, _wav)| StartResponse {
-      session_id: sid,
-      first_chunk: "".into(),
-    })
-    .map_err(|e| e.to_string())
+fn start_recording_cmd(
+  app: tauri::AppHandle,
+  state: State<SharedState>,
+  args: Option<StartRecordingArgs>,
+) -> Result<StartResponse, String> {
+  let args = args.unwrap_or_default();
+  let mut lock = state.recorder.lock();
+  let result = start_recording(&mut lock, app.clone(), args.device_name, args.config, args.label);
+  emit_state(&app, &mut lock, false);
+  result.map(start_response).map_err(|e| e.to_string())
 }
 
+fn start_response(rec: StartedRecording) -> StartResponse {
+  StartResponse {
+    session_id: rec.session_id,
+    first_chunk: "".into(),
+    config: rec.config,
+    already_running: rec.already_running,
+  }
+}
+
+#[derive(Deserialize)]
+struct ResumeSessionArgs {
+  session_id: String,
+  device_name: Option<String>,
+  // Only source and auto-stop fields apply; the format is the file's.
+  config: Option<RecordingConfig>,
+}
+
+// Keeps recording into an existing WAV session; stop as usual.
 #[tauri::command]
-fn pause_recording_cmd(state: State<SharedState>) -> Result<String, String> {
-  let mut lock = state.recorder.lock().unwrap();
-  pause_recording(&mut *lock)
-    .map(|_| "paused".into())
-    .map_err(|e| e.to_string())
+fn resume_session_cmd(
+  app: tauri::AppHandle,
+  state: State<SharedState>,
+  args: ResumeSessionArgs,
+) -> Result<StartResponse, String> {
+  let mut lock = state.recorder.lock();
+  let result =
+    resume_session(&mut lock, app.clone(), &args.session_id, args.device_name, args.config);
+  emit_state(&app, &mut lock, false);
+  result.map(start_response).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn resume_recording_cmd(state: State<SharedState>) -> Result<String, String> {
-  let mut lock = state.recorder.lock().unwrap();
-  resume_recording(&mut *lock)
-    .map(|_| "resumed".into())
-    .map_err(|e| e.to_string())
+fn pause_recording_cmd(app: tauri::AppHandle, state: State<SharedState>) -> Result<String, String> {
+  let mut lock = state.recorder.lock();
+  let result = pause_recording(&mut lock);
+  emit_state(&app, &mut lock, false);
+  result.map(|_| "paused".into()).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn stop_recording_cmd(state: State<SharedState>) -> Result<StopResponse, String> {
-  let mut lock = state.recorder.lock().unwrap();
-  stop_recording(&mut *lock)
-    .map(|wav| StopResponse {
-      message: "stopped".into(),
-      final_wav: Some(wav.to_string_lossy().to_string()),
-    })
+fn resume_recording_cmd(app: tauri::AppHandle, state: State<SharedState>) -> Result<String, String> {
+  let mut lock = state.recorder.lock();
+  let result = resume_recording(&mut lock);
+  emit_state(&app, &mut lock, false);
+  result.map(|_| "resumed".into()).map_err(|e| e.to_string())
+}
+
+// Discards the running recording (nothing is saved) and leaves the recorder
+// ready for a new one.
+#[tauri::command]
+fn cancel_recording_cmd(app: tauri::AppHandle, state: State<SharedState>) -> Result<String, String> {
+  let mut lock = state.recorder.lock();
+  let result = cancel_recording(&mut lock);
+  emit_state(&app, &mut lock, result.is_ok());
+  result.map(|_| "cancelled".into()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn stop_recording_cmd(
+  app: tauri::AppHandle,
+  state: State<SharedState>,
+) -> Result<StopResponse, String> {
+  let mut lock = state.recorder.lock();
+  let result = stop_recording(&mut lock);
+  emit_state(&app, &mut lock, result.is_ok());
+  result.map(stop_response).map_err(|e| e.to_string())
+}
+
+fn stop_response(rec: StoppedRecording) -> StopResponse {
+  StopResponse {
+    // "cancelled" when stopped during the countdown or discarded as too
+    // short; no file then.
+    message: if rec.path.is_some() { "stopped" } else { "cancelled" }.into(),
+    final_path: rec.path.map(|p| p.to_string_lossy().to_string()),
+    device_name: rec.device_name,
+    duration_secs: rec.duration_secs,
+    clipping_pct: rec.clipping_pct,
+  }
+}
+
+// Flattened into `{ action: "started" | "stopped", ...response }`.
+#[derive(Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum ToggleResponse {
+  Started(StartResponse),
+  Stopped(StopResponse),
+}
+
+// One command for a start/stop hotkey. The recorder lock is held from the
+// check through the start or stop, so two quick presses can't both start.
+// `args` only matters when it starts.
+#[tauri::command]
+fn toggle_recording_cmd(
+  app: tauri::AppHandle,
+  state: State<SharedState>,
+  args: Option<StartRecordingArgs>,
+) -> Result<ToggleResponse, String> {
+  let mut lock = state.recorder.lock();
+  if recording_status(&mut lock).active {
+    let result = stop_recording(&mut lock);
+    emit_state(&app, &mut lock, result.is_ok());
+    return result
+      .map(|rec| ToggleResponse::Stopped(stop_response(rec)))
+      .map_err(|e| e.to_string());
+  }
+  let args = args.unwrap_or_default();
+  let result = start_recording(&mut lock, app.clone(), args.device_name, args.config, args.label);
+  emit_state(&app, &mut lock, false);
+  result
+    .map(|rec| ToggleResponse::Started(start_response(rec)))
     .map_err(|e| e.to_string())
 }
 
+#[derive(Deserialize, Default)]
+struct AddMarkerArgs {
+  label: Option<String>,
+}
+
+// Resolves to the approximate time of the marker in seconds.
+#[tauri::command]
+fn add_marker_cmd(state: State<SharedState>, args: Option<AddMarkerArgs>) -> Result<f64, String> {
+  let args = args.unwrap_or_default();
+  let mut lock = state.recorder.lock();
+  add_marker(&mut lock, args.label).map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct SetInputGainArgs {
+  gain_db: f32,
+}
+
+// Live gain change while recording; resolves to the clamped dB applied.
+#[tauri::command]
+fn set_input_gain_cmd(state: State<SharedState>, args: SetInputGainArgs) -> Result<f32, String> {
+  let mut lock = state.recorder.lock();
+  set_input_gain(&mut lock, args.gain_db).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn recording_status_cmd(state: State<SharedState>) -> RecorderStatus {
+  let mut lock = state.recorder.lock();
+  recording_status(&mut lock)
+}
+
+#[derive(Deserialize, Default)]
+struct TestMicrophoneArgs {
+  device_name: Option<String>,
+  // Clamped to 1..=10; None = 3.
+  seconds: Option<f64>,
+}
+
+// Listens to the mic briefly without saving anything; recording://level
+// events come in meanwhile. Refused while a recording is running, and
+// recordings are refused until it's done.
+#[tauri::command]
+async fn test_microphone_cmd(
+  app: tauri::AppHandle,
+  state: State<'_, SharedState>,
+  args: Option<TestMicrophoneArgs>,
+) -> Result<MicTestReport, String> {
+  let claim = begin_mic_test(&mut state.recorder.lock()).map_err(|e| e.to_string())?;
+  let args = args.unwrap_or_default();
+  tauri::async_runtime::spawn_blocking(move || {
+    test_microphone(claim, &app, args.device_name.as_deref(), args.seconds.unwrap_or(3.0))
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_input_devices_cmd() -> Result<Vec<DeviceInfo>, String> {
+  list_input_devices().map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize, Default)]
+struct DeviceCapabilitiesArgs {
+  // None = system default.
+  device_name: Option<String>,
+}
+
+#[tauri::command]
+fn device_capabilities_cmd(
+  args: Option<DeviceCapabilitiesArgs>,
+) -> Result<DeviceCapabilities, String> {
+  let args = args.unwrap_or_default();
+  device_capabilities(args.device_name.as_deref()).map_err(|e| e.to_string())
+}
+
 /* -------- Save audio from frontend (base64 data URL) to Downloads -------- */
 
+const DEFAULT_MAX_SAVE_MB: u64 = 200;
+
+// Lowercased extension if it's one sessions can have, or a
+// `[bad-extension]` error; this keeps the hint from smuggling in a path or
+// an executable extension.
+fn save_extension(ext_hint: Option<&str>) -> Result<String, String> {
+  let ext = ext_hint.unwrap_or("webm").trim().trim_start_matches('.').to_ascii_lowercase();
+  if !RECORDING_EXTENSIONS.contains(&ext.as_str()) {
+    return Err(format!(
+      "[bad-extension] {ext:?} is not an accepted audio extension (expected one of {})",
+      RECORDING_EXTENSIONS.join(", ")
+    ));
+  }
+  Ok(ext)
+}
+
+// Decodes in chunks straight into the file, so a large recording isn't
+// held in memory a second time as one decoded buffer.
+fn write_base64_file(path: &Path, data: &str) -> Result<u64, String> {
+  let mut decoder = DecoderReader::new(data.as_bytes(), &general_purpose::STANDARD);
+  let file = fs::File::create(path).map_err(|e| format!("write failed: {e}"))?;
+  let mut out = BufWriter::new(file);
+  // The decoder reports bad input as InvalidData; anything else is the disk.
+  let written = io::copy(&mut decoder, &mut out).map_err(|e| match e.kind() {
+    io::ErrorKind::InvalidData => format!("Failed to decode audio: {e}"),
+    _ => format!("write failed: {e}"),
+  })?;
+  out.flush().map_err(|e| format!("write failed: {e}"))?;
+  Ok(written)
+}
+
+#[derive(Serialize)]
+struct SavedAudio {
+  // The blob as sent.
+  path: String,
+  // `<id>.wav` (16 kHz mono) when a transcode was asked for and worked.
+  wav_path: Option<String>,
+  // Why the transcode failed; the raw file is saved regardless.
+  transcode_error: Option<String>,
+  // Why the sidecar couldn't be written; the file is saved but won't show
+  // in list_sessions_cmd until recovered.
+  register_error: Option<String>,
+}
+
+// Saves a browser recording into storage_dir() as a new session, next to
+// what the recorder writes, with a sidecar so it shows in list_sessions_cmd.
+// With `transcode`, a non-WAV blob (MediaRecorder's WebM/Opus) also gets a
+// WAV copy via ffmpeg that whisper can read; the session then uses that.
 #[tauri::command]
-fn save_audio_base64(
-  app_handle: tauri::AppHandle,
+async fn save_audio_base64(
   base64_data: String,
   ext_hint: Option<String>,
-) -> Result<String, String> {
-  let ext = ext_hint.unwrap_or_else(|| "webm".to_string());
+  transcode: Option<bool>,
+) -> Result<SavedAudio, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    save_audio(&base64_data, ext_hint.as_deref(), transcode.unwrap_or(false))
+  })
+  .await
+  .map_err(|e| e.to_string())?
+}
+
+fn save_audio(
+  base64_data: &str,
+  ext_hint: Option<&str>,
+  transcode: bool,
+) -> Result<SavedAudio, String> {
+  let ext = save_extension(ext_hint)?;
 
   // Decode data URL or raw base64
-  let cleaned = base64_data.split(',').last().unwrap_or(&base64_data);
-  let bytes = general_purpose::STANDARD
-    .decode(cleaned)
-    .map_err(|e| format!("Failed to decode audio: {e}"))?;
-
-  // Unique filename
-  let ts = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
-  let filename = format!("recording_{ts}.{ext}");
-
-  // 1) Try OS-native Downloads/ApplesauceCache
-  let mut target_dir: Option<PathBuf> = None;
-  match app_handle.path().download_dir() {
-    Ok(mut d) => {
-      d.push("ApplesauceCache");
-      if let Err(e) = fs::create_dir_all(&d) {
-        eprintln!("[save_audio_base64] create Downloads dir failed: {e}");
-      } else {
-        target_dir = Some(d);
-      }
-    }
-    Err(e) => eprintln!("[save_audio_base64] download_dir unavailable: {e}"),
+  let cleaned = base64_data.split(',').next_back().unwrap_or(base64_data);
+  // Checked on the encoded length (4 chars per 3 bytes) before decoding, so
+  // an oversized payload is refused without writing anything.
+  let max_mb = load_settings().max_save_mb.unwrap_or(DEFAULT_MAX_SAVE_MB);
+  let decoded_len = cleaned.len() as u64 / 4 * 3;
+  if decoded_len > max_mb * 1024 * 1024 {
+    return Err(format!(
+      "[too-large] audio is about {} MB; the limit is {max_mb} MB",
+      decoded_len / (1024 * 1024)
+    ));
   }
 
-  // 2) Fallback to app private data dir if needed
-  if target_dir.is_none() {
-    match app_handle.path().app_data_dir() {
-      Ok(mut d) => {
-        d.push("ApplesauceCache");
-        if let Err(e) = fs::create_dir_all(&d) {
-          eprintln!("[save_audio_base64] create app_data_dir failed: {e}");
-          return Err(format!("Both download_dir and app_data_dir failed: {e}"));
-        }
-        target_dir = Some(d);
-      }
+  let dir = storage_dir();
+  fs::create_dir_all(&dir).map_err(|e| format!("can't create {}: {e}", dir.display()))?;
+  let reserved = reserve_stem(&dir, &new_session_id()).map_err(|e| e.to_string())?;
+  let session_id = &reserved.id;
+  let filepath = dir.join(format!("{session_id}.{ext}"));
+
+  // Write file; a payload that turns out not to be base64 leaves nothing.
+  if let Err(e) = write_base64_file(&filepath, cleaned) {
+    fs::remove_file(&filepath).ok();
+    return Err(e);
+  }
+  tracing::info!("saved frontend audio to {}", filepath.display());
+
+  let mut wav_path = None;
+  let mut transcode_error = None;
+  if transcode && ext != "wav" {
+    let wav = dir.join(format!("{session_id}.wav"));
+    match convert_to_wav(&filepath, &wav) {
+      Ok(()) => wav_path = Some(wav),
       Err(e) => {
-        eprintln!("[save_audio_base64] app_data_dir unavailable: {e}");
-        return Err("No writable directory available".into());
+        fs::remove_file(&wav).ok();
+        tracing::warn!("transcoding {} failed: {e}", filepath.display());
+        transcode_error = Some(e.to_string());
       }
     }
   }
+  // Registered from the WAV when there is one, which has a header to read.
+  let audio = wav_path.as_ref().unwrap_or(&filepath);
+  let register_error = register_saved_audio(&dir, session_id, audio).err().map(|e| {
+    tracing::warn!("writing metadata for saved audio failed: {e}");
+    e.to_string()
+  });
 
-  let dir = target_dir.unwrap();
-  let filepath = dir.join(&filename);
-
-  // Write file
-  fs::write(&filepath, bytes).map_err(|e| format!("write failed: {e}"))?;
-
-  println!("[save_audio_base64] Saved to: {}", filepath.to_string_lossy());
-
-  Ok(filepath.to_string_lossy().to_string())
+  Ok(SavedAudio {
+    path: filepath.to_string_lossy().to_string(),
+    wav_path: wav_path.map(|p| p.to_string_lossy().to_string()),
+    transcode_error,
+    register_error,
+  })
 }
 
 /* ------------------------------ Transcription ----------------------------- */
 
 #[derive(Deserialize)]
-#[allow(dead_code)]
 struct TranscribeArgs {
   session_id: Option<String>,
   model: Option<String>,
+  // ISO 639-1 code ("en", "es", ...) or "auto"; None = auto-detect.
+  language: Option<String>,
+  // "local" (whisper.cpp, default) or "openai" (needs an API key).
+  backend: Option<String>,
 }
 
 #[derive(Serialize)]
 struct TranscribeOut {
+  // All segment texts joined; kept for callers that only want plain text.
   text: String,
+  segments: Vec<Segment>,
+  // The language used, detected when not forced.
+  language: Option<String>,
+  // True if cancel_transcription_cmd stopped it; segments are partial.
+  cancelled: bool,
+}
+
+// Whisper can take minutes; run it off the main thread so the UI stays live.
+// Finished segments stream out as `transcribe://segment` events meanwhile,
+// with a `transcribe://progress` event after each chunk.
+// The result is saved next to the WAV for subtitle export (unless cancelled).
+#[tauri::command]
+async fn transcribe_latest_cmd(
+  app: tauri::AppHandle,
+  state: State<'_, SharedState>,
+  args: TranscribeArgs,
+) -> Result<TranscribeOut, String> {
+  let cancel = state.transcribe_cancel.clone();
+  cancel.reset();
+  tauri::async_runtime::spawn_blocking(move || {
+    let dir = storage_dir();
+    let wav = resolve_session_wav(&dir, args.session_id.as_deref())?;
+    let transcript = transcribe_session(&app, &dir, &wav, &args, &cancel)?;
+    Ok::<_, anyhow::Error>(TranscribeOut {
+      text: transcript.text,
+      segments: transcript.segments,
+      language: transcript.language,
+      cancelled: transcript.cancelled,
+    })
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+// One recording through the chosen backend, saved next to it unless
+// cancelled. `args.session_id` is ignored; `wav` is what gets transcribed.
+fn transcribe_session(
+  app: &tauri::AppHandle,
+  dir: &Path,
+  wav: &Path,
+  args: &TranscribeArgs,
+  cancel: &CancelToken,
+) -> anyhow::Result<Transcript> {
+  // Catches a WAV whose header the recorder hasn't written yet.
+  validate_wav(wav)?;
+  let language = parse_language(args.language.as_deref())?;
+  let transcript = match Backend::parse(args.backend.as_deref())? {
+    Backend::Local => {
+      let model = resolve_model_path(args.model.as_deref());
+      transcribe_wav(
+        wav,
+        &model,
+        language,
+        cancel,
+        |seg| {
+          app.emit(SEGMENT_EVENT, seg).ok();
+        },
+        |progress| {
+          app.emit(PROGRESS_EVENT, progress).ok();
+        },
+      )?
+    }
+    Backend::OpenAi => {
+      let key = read_api_key()
+        .ok_or_else(|| anyhow::anyhow!("no OpenAI API key saved; add one in settings"))?;
+      // One request, so segments and progress all arrive at the end.
+      let transcript = openai::transcribe_wav(wav, &key, language.as_deref())?;
+      for seg in &transcript.segments {
+        app.emit(SEGMENT_EVENT, seg).ok();
+      }
+      let done = Progress { percent: 100.0, chunk_index: 0, chunk_count: 1 };
+      app.emit(PROGRESS_EVENT, done).ok();
+      transcript
+    }
+  };
+  let id = wav.file_stem().and_then(|s| s.to_str()).filter(|_| !transcript.cancelled);
+  if let Some(id) = id {
+    if let Err(e) = write_transcript(dir, id, &transcript) {
+      tracing::warn!("failed to save transcript for {id}: {e}");
+    }
+  }
+  Ok(transcript)
+}
+
+const TRANSCRIBE_BATCH_EVENT: &str = "transcribe://batch-progress";
+
+// Payload of `transcribe://batch-progress`, sent as each session starts
+// (`done: false`) and again when it finishes.
+#[derive(Clone, Serialize)]
+struct TranscribeBatchProgress {
+  session_id: String,
+  index: usize,
+  total: usize,
+  done: bool,
+  error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TranscribeAllOut {
+  transcribed: Vec<String>,
+  // (session id, error)
+  failed: Vec<(String, String)>,
+  // Sessions the batch didn't get to before it was cancelled.
+  skipped: Vec<String>,
+  cancelled: bool,
+}
+
+// Transcribes, oldest first, every session that has no saved transcript
+// yet. One failing session doesn't stop the rest; cancel_transcription_cmd
+// stops the one in progress (its partial transcript isn't saved) and skips
+// what's left. `args.session_id` is ignored.
+#[tauri::command]
+async fn transcribe_all_cmd(
+  app: tauri::AppHandle,
+  state: State<'_, SharedState>,
+  args: TranscribeArgs,
+) -> Result<TranscribeAllOut, String> {
+  let cancel = state.transcribe_cancel.clone();
+  cancel.reset();
+  tauri::async_runtime::spawn_blocking(move || {
+    let dir = storage_dir();
+    let mut pending: Vec<SessionSummary> = list_sessions_page(&dir, 0, None)?
+      .sessions
+      .into_iter()
+      .filter(|s| !transcript_path(&dir, &s.session_id).exists())
+      .collect();
+    pending.reverse();
+    let total = pending.len();
+    let mut out = TranscribeAllOut {
+      transcribed: Vec::new(),
+      failed: Vec::new(),
+      skipped: Vec::new(),
+      cancelled: false,
+    };
+    for (index, session) in pending.into_iter().enumerate() {
+      if cancel.is_cancelled() {
+        out.cancelled = true;
+        out.skipped.push(session.session_id);
+        continue;
+      }
+      let mut progress = TranscribeBatchProgress {
+        session_id: session.session_id.clone(),
+        index,
+        total,
+        done: false,
+        error: None,
+      };
+      app.emit(TRANSCRIBE_BATCH_EVENT, progress.clone()).ok();
+      let wav = Path::new(&session.wav_path);
+      match transcribe_session(&app, &dir, wav, &args, &cancel) {
+        Ok(t) if t.cancelled => {
+          out.cancelled = true;
+          out.skipped.push(session.session_id);
+        }
+        Ok(_) => out.transcribed.push(session.session_id),
+        Err(e) => {
+          tracing::warn!("batch transcription of {} failed: {e}", session.session_id);
+          progress.error = Some(e.to_string());
+          out.failed.push((session.session_id, e.to_string()));
+        }
+      }
+      progress.done = true;
+      app.emit(TRANSCRIBE_BATCH_EVENT, progress).ok();
+    }
+    Ok::<_, anyhow::Error>(out)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize, Default)]
+struct ModelStatusArgs {
+  // Name or path as given to transcribe_latest_cmd; None = the default.
+  model: Option<String>,
+}
+
+// Which GGML models are installed, so the UI can show "model needed"
+// before a local transcription fails for want of one.
+#[tauri::command]
+fn whisper_model_status_cmd(args: Option<ModelStatusArgs>) -> Result<ModelStatus, String> {
+  let args = args.unwrap_or_default();
+  model_status(args.model.as_deref()).map_err(|e| e.to_string())
 }
 
+// Takes effect at the next chunk boundary; transcribe_latest_cmd then
+// returns what it has with `cancelled: true`.
 #[tauri::command]
-fn transcribe_latest_cmd(_args: TranscribeArgs) -> Result<TranscribeOut, String> {
-  // TODO: Call your local Whisper/Python process here (or Rust whisper.cpp).
-  Ok(TranscribeOut {
-    text: "Transcription placeholder (implement Whisper integration)".into(),
+fn cancel_transcription_cmd(state: State<SharedState>) {
+  state.transcribe_cancel.cancel();
+}
+
+#[derive(Deserialize)]
+struct ExportTranscriptArgs {
+  session_id: String,
+  // "srt" or "vtt"
+  format: String,
+}
+
+#[tauri::command]
+fn export_transcript_cmd(args: ExportTranscriptArgs) -> Result<String, String> {
+  let format = SubtitleFormat::parse(&args.format).map_err(|e| e.to_string())?;
+  export_transcript(&storage_dir(), &args.session_id, format)
+    .map(|p| p.to_string_lossy().to_string())
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct ExportSessionZipArgs {
+  session_id: String,
+  // A folder (the zip is named after the session) or the zip's full path.
+  dest_path: String,
+}
+
+// Everything of one session in a zip, for backup or moving to another
+// machine (import_session_zip_cmd). Resolves to the zip's path.
+#[tauri::command]
+async fn export_session_zip_cmd(args: ExportSessionZipArgs) -> Result<String, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    export_session_zip(&storage_dir(), &args.session_id, Path::new(&args.dest_path))
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map(|p| p.to_string_lossy().to_string())
+  .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct NormalizeArgs {
+  session_id: String,
+  // Defaults to -16 LUFS.
+  target_lufs: Option<f64>,
+}
+
+// Rewrites the session's WAV in place; decoding and filtering a long
+// lecture takes a moment, so it runs off the main thread.
+#[tauri::command]
+async fn normalize_session_cmd(args: NormalizeArgs) -> Result<NormalizeReport, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let target = args.target_lufs.unwrap_or(DEFAULT_TARGET_LUFS);
+    normalize_session(&storage_dir(), &args.session_id, target)
   })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct TrimSilenceArgs {
+  session_id: String,
+  // dBFS below which audio counts as silence; defaults to -45.
+  threshold_db: Option<f64>,
+  // Overwrite the session instead of writing `<id>_trimmed`.
+  #[serde(default)]
+  in_place: bool,
+}
+
+#[tauri::command]
+async fn trim_silence_cmd(args: TrimSilenceArgs) -> Result<TrimReport, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let threshold = args.threshold_db.unwrap_or(DEFAULT_SILENCE_DB);
+    trim_silence(&storage_dir(), &args.session_id, threshold, args.in_place)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct MergeSessionsArgs {
+  // Joined in this order; the first one's format wins.
+  session_ids: Vec<String>,
+  // None = `<first id>_merged`.
+  output_name: Option<String>,
+  // Convert parts with a different rate/channel count instead of failing.
+  #[serde(default = "default_true")]
+  allow_resample: bool,
+}
+
+fn default_true() -> bool {
+  true
+}
+
+#[tauri::command]
+async fn merge_sessions_cmd(args: MergeSessionsArgs) -> Result<SessionSummary, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    merge_sessions(
+      &storage_dir(),
+      &args.session_ids,
+      args.output_name.as_deref(),
+      args.allow_resample,
+    )
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct SplitSessionArgs {
+  session_id: String,
+  at_secs: f64,
+}
+
+// Resolves to the two new sessions, `<id>_part1` and `<id>_part2`.
+#[tauri::command]
+async fn split_session_cmd(args: SplitSessionArgs) -> Result<Vec<SessionSummary>, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    split_session(&storage_dir(), &args.session_id, args.at_secs)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct WaveformPeaksArgs {
+  session_id: String,
+  // Columns to draw; defaults to 1000.
+  buckets: Option<usize>,
+}
+
+// Flattened [min, max] pairs per bucket, cached in `<id>.peaks.json`.
+#[tauri::command]
+async fn waveform_peaks_cmd(args: WaveformPeaksArgs) -> Result<Vec<f32>, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let buckets = args.buckets.unwrap_or(DEFAULT_BUCKETS);
+    waveform_peaks(&storage_dir(), &args.session_id, buckets)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+/* -------------------------------- Playback -------------------------------- */
+
+#[derive(Deserialize)]
+struct PlaySessionArgs {
+  session_id: String,
+  // Where to start; defaults to the beginning.
+  start_secs: Option<f64>,
+}
+
+// Plays through the default output device, replacing any current playback.
+// Position arrives as `playback://position` events, then `playback://ended`.
+#[tauri::command]
+fn play_session_cmd(
+  app: tauri::AppHandle,
+  state: State<SharedState>,
+  args: PlaySessionArgs,
+) -> Result<PlaybackStarted, String> {
+  validate_session_id(&args.session_id).map_err(|e| e.to_string())?;
+  let path = find_recording(&storage_dir(), &args.session_id)
+    .ok_or_else(|| format!("no recording found for session '{}'", args.session_id))?;
+  let mut lock = state.player.lock();
+  play_session(&mut lock, app, args.session_id, path, args.start_secs.unwrap_or(0.0))
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn pause_playback_cmd(state: State<SharedState>) -> Result<(), String> {
+  pause_playback(&mut state.player.lock()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn resume_playback_cmd(state: State<SharedState>) -> Result<(), String> {
+  resume_playback(&mut state.player.lock()).map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct SeekPlaybackArgs {
+  secs: f64,
+}
+
+#[tauri::command]
+fn seek_playback_cmd(state: State<SharedState>, args: SeekPlaybackArgs) -> Result<(), String> {
+  seek_playback(&mut state.player.lock(), args.secs).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn stop_playback_cmd(state: State<SharedState>) {
+  stop_playback(&mut state.player.lock());
 }
 
 /* ----------- Imports / Storage / API key / Prompt (now use storage_dir) ----------- */
@@ -168,31 +864,151 @@ fn transcribe_latest_cmd(_args: TranscribeArgs) -> Result<TranscribeOut, String>
 #[derive(Deserialize)]
 struct ImportAudioArgs {
   path: String,
+  // Upload guard; None = DEFAULT_MAX_IMPORT_MB.
+  max_size_mb: Option<u64>,
+  // Import even when the same file is already a session.
+  #[serde(default)]
+  force: bool,
+}
+// Copies the file in as a new session, converting to 16 kHz mono WAV via
+// ffmpeg unless it already is one. A file that's already imported comes back
+// as the existing session with `duplicate: true`.
+#[tauri::command]
+async fn import_audio_file_cmd(args: ImportAudioArgs) -> Result<ImportedAudio, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let max_mb = args.max_size_mb.unwrap_or(DEFAULT_MAX_IMPORT_MB);
+    import_audio(Path::new(&args.path), &storage_dir(), max_mb, args.force)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct ImportSessionZipArgs {
+  path: String,
+}
+
+// Unpacks a bundle from export_session_zip_cmd as a new session (fresh id).
+#[tauri::command]
+async fn import_session_zip_cmd(args: ImportSessionZipArgs) -> Result<SessionSummary, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    import_session_zip(&storage_dir(), Path::new(&args.path))
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+const BATCH_PROGRESS_EVENT: &str = "import://batch-progress";
+
+#[derive(Deserialize)]
+struct ImportAudioFilesArgs {
+  paths: Vec<String>,
+  max_size_mb: Option<u64>,
+}
+
+// Payload of `import://batch-progress`, one per file as it finishes.
+#[derive(Clone, Serialize)]
+struct BatchProgress {
+  index: usize,
+  total: usize,
+  path: String,
+  error: Option<String>,
+}
+
+// Failures are reported per file instead of aborting the rest. Videos in
+// the list are handled like import_video_file_cmd.
+#[tauri::command]
+async fn import_audio_files_cmd(
+  app: tauri::AppHandle,
+  args: ImportAudioFilesArgs,
+) -> Result<Vec<Result<SessionSummary, String>>, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let dir = storage_dir();
+    let max_mb = args.max_size_mb.unwrap_or(DEFAULT_MAX_IMPORT_MB);
+    let total = args.paths.len();
+    args
+      .paths
+      .iter()
+      .enumerate()
+      .map(|(index, path)| {
+        let result = import_any(Path::new(path), &dir, max_mb).map_err(|e| e.to_string());
+        let progress = BatchProgress {
+          index,
+          total,
+          path: path.clone(),
+          error: result.as_ref().err().cloned(),
+        };
+        app.emit(BATCH_PROGRESS_EVENT, progress).ok();
+        result
+      })
+      .collect()
+  })
+  .await
+  .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct ImportVideoArgs {
+  path: String,
 }
+// .mp4/.mov/... lectures: ffmpeg pulls the audio track out into a session.
 #[tauri::command]
-fn import_audio_file_cmd(args: ImportAudioArgs) -> Result<String, String> {
-  // TODO: validate + copy into storage_dir()/session if desired
-  Ok(format!("Imported audio: {}", args.path))
+async fn import_video_file_cmd(args: ImportVideoArgs) -> Result<SessionSummary, String> {
+  tauri::async_runtime::spawn_blocking(move || import_video(Path::new(&args.path), &storage_dir()))
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct AudioInfoArgs {
+  path: String,
+}
+
+// Sample rate, channels, length etc. of a file picked for import, read from
+// its header without decoding.
+#[tauri::command]
+async fn audio_info_cmd(args: AudioInfoArgs) -> Result<AudioInfo, String> {
+  tauri::async_runtime::spawn_blocking(move || audio_info(Path::new(&args.path)))
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
 }
 
 #[derive(Deserialize)]
 struct ImportYoutubeArgs {
   url: String,
 }
+// Returns the downloaded WAV's path; it shows up as a session.
 #[tauri::command]
-fn import_youtube_audio_cmd(args: ImportYoutubeArgs) -> Result<String, String> {
-  // TODO: spawn yt-dlp (cross-platform path), then transcribe (use tauri-plugin-shell).
-  Ok(format!("(stub) would download: {}", args.url))
+async fn import_youtube_audio_cmd(
+  app: tauri::AppHandle,
+  args: ImportYoutubeArgs,
+) -> Result<String, String> {
+  youtube::download_audio(&app, &args.url, &storage_dir())
+    .await
+    .map(|p| p.to_string_lossy().to_string())
+    .map_err(|e| e.to_string())
 }
 
 #[derive(Deserialize)]
 struct ImportPdfArgs {
   path: String,
+  // OCR image-only PDFs with tesseract; off by default since it's slow.
+  #[serde(default)]
+  allow_ocr: bool,
 }
+// Writes the text next to recordings as <name>.txt for notes generation.
 #[tauri::command]
-fn import_pdf_file_cmd(args: ImportPdfArgs) -> Result<String, String> {
-  // TODO: parse PDF -> text -> notes
-  Ok(format!("(stub) PDF queued: {}", args.path))
+async fn import_pdf_file_cmd(args: ImportPdfArgs) -> Result<PdfImport, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    import_pdf(Path::new(&args.path), &storage_dir(), args.allow_ocr)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -202,46 +1018,308 @@ fn open_storage_dir_cmd(_app: tauri::AppHandle) -> Result<String, String> {
     return Err(e.to_string());
   }
   if let Err(e) = open::that(&p) {
-    eprintln!("open folder failed: {e}");
+    tracing::warn!("open folder failed: {e}");
   }
   Ok(p.to_string_lossy().to_string())
 }
 
+// Opens the file manager with the session's recording selected, so it's
+// easy to find in a cluttered Downloads folder.
 #[tauri::command]
-fn clear_storage_dir_cmd() -> Result<String, String> {
-  let p = storage_dir();
-  if p.exists() {
-    if let Ok(entries) = std::fs::read_dir(&p) {
-      for entry in entries.flatten() {
-        let _ = std::fs::remove_file(entry.path()); // ignore per-file errors
+fn reveal_session_cmd(args: SessionIdArgs) -> Result<(), String> {
+  validate_session_id(&args.session_id).map_err(|e| e.to_string())?;
+  let path = find_recording(&storage_dir(), &args.session_id)
+    .ok_or_else(|| format!("session '{}' not found", args.session_id))?;
+  reveal_path(&path).map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize, Default)]
+struct ListSessionsArgs {
+  #[serde(default)]
+  offset: usize,
+  // None = everything from `offset` on.
+  limit: Option<usize>,
+}
+
+#[tauri::command]
+fn list_sessions_cmd(args: Option<ListSessionsArgs>) -> Result<SessionPage, String> {
+  let args = args.unwrap_or_default();
+  list_sessions_page(&storage_dir(), args.offset, args.limit).map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct SessionIdArgs {
+  session_id: String,
+}
+
+#[derive(Deserialize, Default)]
+struct RecoverSessionsArgs {
+  // Move WAVs that can't be salvaged to the trash instead of reporting them.
+  #[serde(default)]
+  discard_unreadable: bool,
+}
+
+// Also runs at launch (without discarding), so the UI can call it then to
+// show what was recovered and offer to discard the rest.
+#[tauri::command]
+async fn recover_sessions_cmd(
+  app: tauri::AppHandle,
+  args: Option<RecoverSessionsArgs>,
+) -> Result<RecoveryReport, String> {
+  let args = args.unwrap_or_default();
+  tauri::async_runtime::spawn_blocking(move || {
+    // Held throughout, so no recording can start and have its live file
+    // swept out of `.tmp`.
+    let state = app.state::<SharedState>();
+    let mut recorder = state.recorder.lock();
+    if recording_status(&mut recorder).active {
+      return Err(anyhow::anyhow!("can't recover sessions while recording"));
+    }
+    recover_sessions(&storage_dir(), args.discard_unreadable)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+// Moves the files to storage_dir()/.trash; restore_session_cmd undoes it.
+#[tauri::command]
+fn delete_session_cmd(args: SessionIdArgs) -> Result<Vec<String>, String> {
+  trash::trash_session(&storage_dir(), &args.session_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn restore_session_cmd(args: SessionIdArgs) -> Result<Vec<String>, String> {
+  trash::restore_session(&storage_dir(), &args.session_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_trash_cmd() -> Vec<trash::TrashEntry> {
+  trash::read_manifest(&storage_dir())
+}
+
+#[tauri::command]
+fn empty_trash_cmd() -> ClearSummary {
+  trash::empty_trash(&storage_dir())
+}
+
+#[derive(Deserialize)]
+struct RenameSessionArgs {
+  session_id: String,
+  new_name: String,
+}
+#[tauri::command]
+fn rename_session_cmd(args: RenameSessionArgs) -> Result<String, String> {
+  rename_session(&storage_dir(), &args.session_id, &args.new_name).map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct SetSessionTagsArgs {
+  session_id: String,
+  tags: Vec<String>,
+}
+
+// Resolves to the tags as saved (trimmed, deduplicated).
+#[tauri::command]
+fn set_session_tags_cmd(args: SetSessionTagsArgs) -> Result<Vec<String>, String> {
+  set_session_tags(&storage_dir(), &args.session_id, &args.tags).map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct SearchSessionsArgs {
+  query: String,
+}
+
+// Case-insensitive substring match on name, tags and transcript text. Reads
+// every transcript, so it runs off the main thread.
+#[tauri::command]
+async fn search_sessions_cmd(args: SearchSessionsArgs) -> Result<Vec<SessionSummary>, String> {
+  tauri::async_runtime::spawn_blocking(move || search_sessions(&storage_dir(), &args.query))
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct SearchTranscriptsArgs {
+  query: String,
+}
+
+// One result per matching transcript segment, with its time for seeking.
+// Transcripts stay indexed in memory between searches.
+#[tauri::command]
+async fn search_transcripts_cmd(
+  args: SearchTranscriptsArgs,
+) -> Result<Vec<TranscriptMatch>, String> {
+  tauri::async_runtime::spawn_blocking(move || search_transcripts(&storage_dir(), &args.query))
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+const MIGRATE_PROGRESS_EVENT: &str = "storage://migrate-progress";
+
+#[derive(Deserialize)]
+struct SetStorageDirArgs {
+  // None = back to the default Downloads/ApplesauceCacheNative.
+  path: Option<String>,
+  // Move existing recordings from the old folder into the new one.
+  #[serde(default)]
+  move_existing: bool,
+}
+
+#[derive(Serialize)]
+struct StorageDirChange {
+  previous: String,
+  current: String,
+  // Present when move_existing was set.
+  moved: Option<MoveReport>,
+}
+
+#[derive(Clone, Serialize)]
+struct MigrateProgress {
+  done: usize,
+  total: usize,
+}
+
+// Without move_existing only new files go to the new folder; the old path is
+// returned so the UI can offer to move them. Moves fall back to copy+delete
+// across drives, emitting `storage://migrate-progress` per entry.
+#[tauri::command]
+async fn set_storage_dir_cmd(
+  app: tauri::AppHandle,
+  state: State<'_, SharedState>,
+  args: SetStorageDirArgs,
+) -> Result<StorageDirChange, String> {
+  // The open WAV would be moved out from under the recorder.
+  if args.move_existing && recording_status(&mut state.recorder.lock()).active {
+    return Err("stop the current recording before moving recordings".into());
+  }
+  tauri::async_runtime::spawn_blocking(move || {
+    let previous = storage_dir();
+    let target = match &args.path {
+      Some(p) => validate_storage_dir(Path::new(p))?,
+      None => default_storage_dir(),
+    };
+    let same = previous.canonicalize().ok() == target.canonicalize().ok();
+    if args.move_existing && !same {
+      // Moving into a subfolder of itself (or the reverse) would copy the
+      // target into itself forever.
+      fs::create_dir_all(&target)?;
+      if let (Ok(from), Ok(to)) = (previous.canonicalize(), target.canonicalize()) {
+        if to.starts_with(&from) || from.starts_with(&to) {
+          return Err(anyhow::anyhow!("the new folder can't be inside the current one, or contain it"));
+        }
       }
     }
+    let moved = (args.move_existing && !same).then(|| {
+      move_dir_contents(&previous, &target, |done, total| {
+        app.emit(MIGRATE_PROGRESS_EVENT, MigrateProgress { done, total }).ok();
+      })
+    });
+    let value = match &args.path {
+      Some(_) => serde_json::Value::String(target.to_string_lossy().to_string()),
+      None => serde_json::Value::Null,
+    };
+    update_settings(serde_json::json!({ "storage_dir": value }))?;
+    Ok::<_, anyhow::Error>(StorageDirChange {
+      previous: previous.to_string_lossy().to_string(),
+      current: storage_dir().to_string_lossy().to_string(),
+      moved,
+    })
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn storage_usage_cmd() -> Result<StorageUsage, String> {
+  tauri::async_runtime::spawn_blocking(|| storage_usage(&storage_dir()))
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct CleanupArgs {
+  // None = the retention_days setting.
+  max_age_days: Option<u32>,
+  // Only report what would be deleted.
+  #[serde(default)]
+  dry_run: bool,
+}
+#[tauri::command]
+fn cleanup_old_sessions_cmd(args: CleanupArgs) -> Result<Vec<String>, String> {
+  let days = args
+    .max_age_days
+    .or(load_settings().retention_days)
+    .ok_or("no age given and no retention period set")?;
+  cleanup_old_sessions(&storage_dir(), days, args.dry_run).map_err(|e| e.to_string())
+}
+
+// Everything goes to the trash, so this is undoable until empty_trash_cmd.
+#[tauri::command]
+fn clear_storage_dir_cmd(state: State<'_, SharedState>) -> Result<ClearSummary, String> {
+  // The session being recorded would lose its files as soon as it stops.
+  if recording_status(&mut state.recorder.lock()).active {
+    return Err("stop the current recording before clearing storage".into());
   }
-  Ok("cleared".into())
+  Ok(trash::trash_all(&storage_dir()))
 }
 
-// NOTE: For now, we also store API key & prompt in storage_dir() so your app compiles.
-// If you prefer private app data later, we can switch these to app_handle.path().app_data_dir().
+// The API key lives in the OS keychain (secrets.rs); everything else is in
+// settings.json (settings.rs).
 #[derive(Deserialize)]
 struct SaveApiKeyArgs {
   value: String,
 }
 #[tauri::command]
 fn save_api_key_cmd(args: SaveApiKeyArgs) -> Result<(), String> {
-  let p = storage_dir().join("apikey.txt");
-  std::fs::create_dir_all(storage_dir()).map_err(|e| e.to_string())?;
-  std::fs::write(p, args.value).map_err(|e| e.to_string())
+  secrets::save_api_key(&args.value).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 fn read_api_key_cmd() -> Result<Option<String>, String> {
-  let p = storage_dir().join("apikey.txt");
-  if p.exists() {
-    let s = std::fs::read_to_string(p).map_err(|e| e.to_string())?;
-    Ok(Some(s))
-  } else {
-    Ok(None)
-  }
+  secrets::read_api_key().map_err(|e| e.to_string())
+}
+
+// For the settings screen: whether a key is saved, without exposing it.
+#[tauri::command]
+fn has_api_key_cmd() -> bool {
+  read_api_key().is_some()
+}
+
+#[derive(Serialize)]
+struct VerifyKeyOut {
+  valid: bool,
+  error: Option<String>,
+}
+
+// Checks the saved key (or `value`, before saving) against OpenAI.
+#[tauri::command]
+async fn verify_api_key_cmd(args: Option<SaveApiKeyArgs>) -> Result<VerifyKeyOut, String> {
+  let key = match args {
+    Some(a) => secrets::validate_api_key(&a.value).map_err(|e| e.to_string())?,
+    None => read_api_key().ok_or("no API key saved")?,
+  };
+  let result = tauri::async_runtime::spawn_blocking(move || openai::verify_key(&key))
+    .await
+    .map_err(|e| e.to_string())?;
+  Ok(match result {
+    Ok(true) => VerifyKeyOut { valid: true, error: None },
+    Ok(false) => VerifyKeyOut {
+      valid: false,
+      error: Some("OpenAI rejected this API key".into()),
+    },
+    Err(e) => VerifyKeyOut { valid: false, error: Some(e.to_string()) },
+  })
+}
+
+// Trimmed key for backends; None when unset or unreadable.
+fn read_api_key() -> Option<String> {
+  let key = secrets::read_api_key().ok()??;
+  let key = key.trim();
+  (!key.is_empty()).then(|| key.to_string())
 }
 
 #[derive(Deserialize)]
@@ -250,22 +1328,138 @@ struct SetPromptArgs {
 }
 #[tauri::command]
 fn set_prompt_preset_cmd(args: SetPromptArgs) -> Result<(), String> {
-  let p = storage_dir().join("prompt.txt");
-  std::fs::create_dir_all(storage_dir()).map_err(|e| e.to_string())?;
-  std::fs::write(p, args.value).map_err(|e| e.to_string())
+  update_settings(serde_json::json!({ "prompt_preset": args.value }))
+    .map(|_| ())
+    .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 fn get_prompt_preset_cmd() -> Result<Option<String>, String> {
-  let p = storage_dir().join("prompt.txt");
-  if p.exists() {
-    let s = std::fs::read_to_string(p).map_err(|e| e.to_string())?;
-    Ok(Some(s))
-  } else {
-    Ok(None)
+  Ok(load_settings().prompt_preset)
+}
+
+#[derive(Serialize)]
+struct LastRecordingConfig {
+  device_name: Option<String>,
+  config: Option<RecordingConfig>,
+}
+
+// What start_recording_cmd falls back to when it's given no device or
+// config: whatever the previous recording used.
+#[tauri::command]
+fn get_last_recording_config_cmd() -> LastRecordingConfig {
+  let settings = load_settings();
+  LastRecordingConfig {
+    device_name: settings.last_device_name,
+    config: settings.last_recording_config,
   }
 }
 
+#[tauri::command]
+fn get_settings_cmd() -> Settings {
+  load_settings()
+}
+
+// `patch` is a partial Settings object; omitted keys keep their value.
+#[derive(Deserialize)]
+struct UpdateSettingsArgs {
+  patch: serde_json::Value,
+}
+#[tauri::command]
+fn update_settings_cmd(args: UpdateSettingsArgs) -> Result<Settings, String> {
+  update_settings(args.patch).map_err(|e| e.to_string())
+}
+
+#[derive(Serialize)]
+struct NotesOut {
+  path: String,
+  markdown: String,
+}
+
+// Streams the reply as `notes://token` events while it is written.
+#[tauri::command]
+async fn generate_notes_cmd(
+  app: tauri::AppHandle,
+  args: SessionIdArgs,
+) -> Result<NotesOut, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let key = read_api_key();
+    let prompt = load_settings().prompt_preset;
+    notes::generate_notes(
+      &storage_dir(),
+      &args.session_id,
+      key.as_deref(),
+      prompt.as_deref(),
+      |token| {
+        app.emit(notes::TOKEN_EVENT, token).ok();
+      },
+    )
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map(|(path, markdown)| NotesOut {
+    path: path.to_string_lossy().to_string(),
+    markdown,
+  })
+  .map_err(|e| e.to_string())
+}
+
+// Flashcards from the session's notes/transcript, via the LLM when a key is
+// saved; returns the .tsv path for Quizlet's import box.
+#[tauri::command]
+async fn export_quizlet_tsv_cmd(args: SessionIdArgs) -> Result<String, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let dir = storage_dir();
+    let key = read_api_key();
+    let prompt = load_settings().prompt_preset;
+    let cards = flashcards::make_cards(&dir, &args.session_id, key.as_deref(), prompt.as_deref())?;
+    flashcards::write_quizlet_tsv(&dir, &args.session_id, &cards)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map(|p| p.to_string_lossy().to_string())
+  .map_err(|e| e.to_string())
+}
+
+// Same cards as the Quizlet export, packaged as an Anki deck.
+#[tauri::command]
+async fn export_anki_cmd(args: SessionIdArgs) -> Result<String, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    let dir = storage_dir();
+    let key = read_api_key();
+    let prompt = load_settings().prompt_preset;
+    let cards = flashcards::make_cards(&dir, &args.session_id, key.as_deref(), prompt.as_deref())?;
+    anki::write_apkg(&dir, &args.session_id, &cards)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map(|p| p.to_string_lossy().to_string())
+  .map_err(|e| e.to_string())
+}
+
+// One report for the About/Support panel: version, OS, mic, storage, model
+// and whether an API key is set (never the key itself).
+#[tauri::command]
+async fn diagnostics_cmd() -> Result<diagnostics::Diagnostics, String> {
+  tauri::async_runtime::spawn_blocking(|| diagnostics::collect(read_api_key().is_some()))
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// The folder holding the daily `applesauce.YYYY-MM-DD.log` files.
+#[tauri::command]
+fn get_log_path_cmd() -> String {
+  logging::logs_dir().to_string_lossy().to_string()
+}
+
+#[tauri::command]
+fn open_logs_cmd() -> Result<String, String> {
+  let dir = logging::logs_dir();
+  fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+  open::that(&dir).map_err(|e| e.to_string())?;
+  Ok(dir.to_string_lossy().to_string())
+}
+
 #[tauri::command]
 fn open_quizlet_cmd() -> Result<(), String> {
   open::that("https://quizlet.com/create-set").map_err(|e| e.to_string())
@@ -274,33 +1468,179 @@ fn open_quizlet_cmd() -> Result<(), String> {
 /* ------------------------------ Tauri entry ------------------------------ */
 
 fn main() {
+  // Held until exit so buffered log lines reach the file.
+  let _log_guard = logging::init();
   tauri::Builder::default()
     .manage(SharedState {
       recorder: Mutex::new(RecorderState::new()),
+      player: Mutex::new(PlayerState::default()),
+      transcribe_cancel: CancelToken::default(),
     })
     .plugin(tauri_plugin_shell::init()) // optional, safe to keep
+    .setup(|app| {
+      // Emits devices://changed so the mic picker stays current.
+      if let Err(e) = spawn_device_watcher(app.handle().clone()) {
+        tracing::error!("starting the device watcher failed: {e}");
+      }
+      // Older builds kept the key and prompt as plain files next to recordings.
+      if let Err(e) = secrets::migrate_plaintext_key(&default_storage_dir().join("apikey.txt")) {
+        tracing::error!("API key migration failed: {e}");
+      }
+      if let Err(e) = settings::migrate_legacy_files(&default_storage_dir()) {
+        tracing::error!("prompt.txt migration failed: {e}");
+      }
+      // Recordings cut off by a crash are left in `.tmp` or without a
+      // sidecar; settle them before anything can record again. Unreadable
+      // ones stay put until the UI asks (recover_sessions_cmd).
+      match recover_sessions(&storage_dir(), false) {
+        Ok(r) if !(r.recovered.is_empty() && r.discarded.is_empty() && r.unreadable.is_empty()) => {
+          tracing::info!(
+            "crash recovery: {} recovered, {} discarded, {} unreadable",
+            r.recovered.len(),
+            r.discarded.len(),
+            r.unreadable.len()
+          )
+        }
+        Ok(_) => {}
+        Err(e) => tracing::error!("crash recovery failed: {e}"),
+      }
+      if let Some(days) = load_settings().retention_days {
+        match cleanup_old_sessions(&storage_dir(), days, false) {
+          Ok(removed) if !removed.is_empty() => {
+            tracing::info!("retention moved {} old session(s) to the trash", removed.len())
+          }
+          Ok(_) => {}
+          Err(e) => tracing::error!("retention cleanup failed: {e}"),
+        }
+      }
+      Ok(())
+    })
     .invoke_handler(tauri::generate_handler![
       // Recording
       start_recording_cmd,
+      resume_session_cmd,
       pause_recording_cmd,
       resume_recording_cmd,
       stop_recording_cmd,
+      cancel_recording_cmd,
+      toggle_recording_cmd,
+      recording_status_cmd,
+      add_marker_cmd,
+      set_input_gain_cmd,
+      list_input_devices_cmd,
+      test_microphone_cmd,
+      device_capabilities_cmd,
       // Frontend audio save
       save_audio_base64,
       // Transcription
       transcribe_latest_cmd,
+      cancel_transcription_cmd,
+      transcribe_all_cmd,
+      whisper_model_status_cmd,
+      export_transcript_cmd,
+      export_session_zip_cmd,
+      normalize_session_cmd,
+      trim_silence_cmd,
+      merge_sessions_cmd,
+      split_session_cmd,
+      waveform_peaks_cmd,
+      // Playback
+      play_session_cmd,
+      pause_playback_cmd,
+      resume_playback_cmd,
+      seek_playback_cmd,
+      stop_playback_cmd,
       // Imports / storage / API key / prompt / external
       import_audio_file_cmd,
+      import_video_file_cmd,
+      audio_info_cmd,
+      import_audio_files_cmd,
+      import_session_zip_cmd,
       import_youtube_audio_cmd,
       import_pdf_file_cmd,
       open_storage_dir_cmd,
+      reveal_session_cmd,
+      list_sessions_cmd,
+      delete_session_cmd,
+      restore_session_cmd,
+      recover_sessions_cmd,
+      list_trash_cmd,
+      empty_trash_cmd,
+      rename_session_cmd,
+      set_session_tags_cmd,
+      search_sessions_cmd,
+      search_transcripts_cmd,
       clear_storage_dir_cmd,
+      cleanup_old_sessions_cmd,
+      storage_usage_cmd,
+      set_storage_dir_cmd,
       save_api_key_cmd,
       read_api_key_cmd,
+      has_api_key_cmd,
+      verify_api_key_cmd,
       set_prompt_preset_cmd,
       get_prompt_preset_cmd,
-      open_quizlet_cmd
+      get_settings_cmd,
+      get_last_recording_config_cmd,
+      update_settings_cmd,
+      open_quizlet_cmd,
+      diagnostics_cmd,
+      get_log_path_cmd,
+      open_logs_cmd,
+      generate_notes_cmd,
+      export_quizlet_tsv_cmd,
+      export_anki_cmd,
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn transcribe_out_keeps_its_field_names() {
+    let segment = |start, end, text: &str| Segment { start, end, text: text.to_string() };
+    let out = TranscribeOut {
+      text: "Hello there. General Kenobi.".to_string(),
+      segments: vec![segment(0.0, 1.5, "Hello there."), segment(1.5, 3.25, "General Kenobi.")],
+      language: Some("en".to_string()),
+      cancelled: false,
+    };
+    let json = serde_json::to_value(&out).unwrap();
+    assert_eq!(
+      json,
+      serde_json::json!({
+        "text": "Hello there. General Kenobi.",
+        "segments": [
+          { "start": 0.0, "end": 1.5, "text": "Hello there." },
+          { "start": 1.5, "end": 3.25, "text": "General Kenobi." },
+        ],
+        "language": "en",
+        "cancelled": false,
+      })
+    );
+    // The UI reads the segments back as-is.
+    let segments: Vec<Segment> = serde_json::from_value(json["segments"].clone()).unwrap();
+    assert_eq!(segments.len(), 2);
+    assert_eq!((segments[1].start, segments[1].end), (1.5, 3.25));
+    assert_eq!(segments[1].text, "General Kenobi.");
+  }
+
+  #[test]
+  fn recorder_lock_survives_a_panic_while_held() {
+    let recorder = Mutex::new(RecorderState::new());
+    std::thread::scope(|scope| {
+      let crashed = scope.spawn(|| {
+        let _guard = recorder.lock();
+        panic!("command failed while holding the recorder");
+      });
+      assert!(crashed.join().is_err());
+    });
+    // No poisoning: the next command gets the lock and a usable state.
+    let status = recording_status(&mut recorder.lock());
+    assert!(!status.active);
+    assert!(status.session_id.is_none());
+  }
+}
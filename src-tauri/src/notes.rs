@@ -0,0 +1,42 @@
+use anyhow::{anyhow, Result};
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+use crate::openai;
+use crate::transcribe::read_transcript;
+
+pub const TOKEN_EVENT: &str = "notes://token";
+
+const NOTES_SYSTEM_PROMPT: &str = "You write clear, well-organised study notes in Markdown \
+  from lecture transcripts: headings for topics, bullet points for key ideas, and bold for \
+  terms worth memorising. Do not invent content that isn't in the transcript.";
+
+pub fn notes_path(dir: &Path, session_id: &str) -> PathBuf {
+  dir.join(format!("{session_id}.notes.md"))
+}
+
+/// Turns the session's transcript into Markdown notes with the LLM, saves
+/// them as `<session_id>.notes.md` and returns the path and text. The user's
+/// prompt preset is added to the instructions. Tokens go to `on_token` as
+/// they stream in.
+pub fn generate_notes(
+  dir: &Path,
+  session_id: &str,
+  api_key: Option<&str>,
+  prompt_preset: Option<&str>,
+  on_token: impl FnMut(&str),
+) -> Result<(PathBuf, String)> {
+  let transcript = read_transcript(dir, session_id)?;
+  let api_key =
+    api_key.ok_or_else(|| anyhow!("no OpenAI API key saved; add one in settings to make notes"))?;
+  let system = match prompt_preset {
+    Some(extra) if !extra.trim().is_empty() => format!("{NOTES_SYSTEM_PROMPT}\n\n{extra}"),
+    _ => NOTES_SYSTEM_PROMPT.to_string(),
+  };
+  let markdown = openai::chat_stream(api_key, &system, &transcript.text, on_token)?;
+  let path = notes_path(dir, session_id);
+  fs::write(&path, &markdown)?;
+  Ok((path, markdown))
+}
@@ -0,0 +1,279 @@
+use anyhow::{anyhow, Result};
+use reqwest::blocking::{multipart, Client, Response};
+use reqwest::header::RETRY_AFTER;
+use serde::Deserialize;
+use std::{
+  fs,
+  io::{BufRead, BufReader},
+  path::Path,
+  thread,
+  time::Duration,
+};
+
+use crate::settings::load_settings;
+use crate::transcribe::{parse_language, Segment, Transcript};
+
+const TRANSCRIPTIONS_URL: &str = "https://api.openai.com/v1/audio/transcriptions";
+const CHAT_URL: &str = "https://api.openai.com/v1/chat/completions";
+const CHAT_MODEL: &str = "gpt-4o-mini";
+// Cheapest authenticated call: lists models, costs nothing.
+const MODELS_URL: &str = "https://api.openai.com/v1/models";
+const MODEL: &str = "whisper-1";
+// The endpoint rejects uploads above this.
+pub const MAX_UPLOAD_BYTES: u64 = 25 * 1024 * 1024;
+// Uploading and transcribing an hour-long lecture takes a while.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_RETRY_BASE_MS: u64 = 1000;
+// Longest wait between attempts, whatever Retry-After says.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct VerboseTranscription {
+  text: String,
+  // Full English name, e.g. "english".
+  language: Option<String>,
+  #[serde(default)]
+  segments: Vec<ApiSegment>,
+}
+
+#[derive(Deserialize)]
+struct ApiSegment {
+  start: f64,
+  end: f64,
+  text: String,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+  choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+  message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+  content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatChunk {
+  choices: Vec<ChunkChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+  delta: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ApiErrorBody {
+  error: ApiError,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+  message: String,
+}
+
+// Turns a non-2xx response into a user-facing error.
+fn api_error(resp: Response, what: &str) -> anyhow::Error {
+  let status = resp.status().as_u16();
+  let detail = resp
+    .json::<ApiErrorBody>()
+    .map(|b| b.error.message)
+    .unwrap_or_default();
+  match status {
+    401 => anyhow!("OpenAI rejected the API key; check it in settings"),
+    429 => anyhow!("OpenAI rate limit or quota reached; wait a minute and retry ({detail})"),
+    code => anyhow!("OpenAI {what} failed with HTTP {code}: {detail}"),
+  }
+}
+
+fn send_chat(api_key: &str, system: &str, user: &str, stream: bool) -> Result<Response> {
+  let body = serde_json::json!({
+    "model": CHAT_MODEL,
+    "stream": stream,
+    "messages": [
+      { "role": "system", "content": system },
+      { "role": "user", "content": user },
+    ],
+  });
+  let resp = Client::builder()
+    .timeout(REQUEST_TIMEOUT)
+    .build()?
+    .post(CHAT_URL)
+    .bearer_auth(api_key)
+    .json(&body)
+    .send()
+    .map_err(|e| anyhow!("could not reach OpenAI: {}", e.without_url()))?;
+  if !resp.status().is_success() {
+    return Err(api_error(resp, "request"));
+  }
+  Ok(resp)
+}
+
+/// One-shot chat completion: returns the assistant's reply text.
+pub fn chat(api_key: &str, system: &str, user: &str) -> Result<String> {
+  let reply: ChatResponse = send_chat(api_key, system, user, false)?.json()?;
+  reply
+    .choices
+    .into_iter()
+    .next()
+    .and_then(|c| c.message.content)
+    .ok_or_else(|| anyhow!("OpenAI returned an empty reply"))
+}
+
+/// Like `chat`, but streams: `on_token` gets each piece of the reply as it
+/// arrives (server-sent events), and the whole reply is returned at the end.
+pub fn chat_stream(
+  api_key: &str,
+  system: &str,
+  user: &str,
+  mut on_token: impl FnMut(&str),
+) -> Result<String> {
+  let resp = send_chat(api_key, system, user, true)?;
+  let mut reply = String::new();
+  for line in BufReader::new(resp).lines() {
+    let line = line?;
+    let Some(data) = line.strip_prefix("data: ") else { continue };
+    if data == "[DONE]" {
+      break;
+    }
+    let Ok(chunk) = serde_json::from_str::<ChatChunk>(data) else { continue };
+    let Some(token) = chunk.choices.into_iter().next().and_then(|c| c.delta.content) else {
+      continue;
+    };
+    on_token(&token);
+    reply.push_str(&token);
+  }
+  if reply.is_empty() {
+    return Err(anyhow!("OpenAI returned an empty reply"));
+  }
+  Ok(reply)
+}
+
+/// Whether OpenAI accepts the key. `Ok(false)` means it was rejected;
+/// `Err` means the check itself couldn't be made (offline, outage).
+pub fn verify_key(api_key: &str) -> Result<bool> {
+  let resp = Client::builder()
+    .timeout(Duration::from_secs(15))
+    .build()?
+    .get(MODELS_URL)
+    .bearer_auth(api_key)
+    .send()
+    .map_err(|e| anyhow!("could not reach OpenAI: {}", e.without_url()))?;
+  match resp.status().as_u16() {
+    200..=299 => Ok(true),
+    401 | 403 => Ok(false),
+    429 => Err(anyhow!("OpenAI rate limit reached; try verifying again shortly")),
+    code => Err(anyhow!("OpenAI returned HTTP {code} while checking the key")),
+  }
+}
+
+fn transcription_form(wav: &Path, language: Option<&str>) -> Result<multipart::Form> {
+  let mut form = multipart::Form::new()
+    .text("model", MODEL)
+    .text("response_format", "verbose_json")
+    .text("timestamp_granularities[]", "segment")
+    .file("file", wav)?;
+  if let Some(code) = language {
+    form = form.text("language", code.to_string());
+  }
+  Ok(form)
+}
+
+// The wait a 429/503 asks for, when it gives one in seconds.
+fn retry_after(resp: &Response) -> Option<Duration> {
+  let secs = resp.headers().get(RETRY_AFTER)?.to_str().ok()?.trim().parse::<u64>().ok()?;
+  Some(Duration::from_secs(secs).min(MAX_RETRY_DELAY))
+}
+
+/// Uploads a WAV to OpenAI and returns it in the same shape as the local
+/// backend. `language` is an ISO 639-1 code, or `None` to let the API
+/// detect it. Rate limits, server errors and dropped connections are
+/// retried with backoff (see settings.openai_max_retries). Errors are
+/// phrased for the user; the key is never included.
+pub fn transcribe_wav(wav: &Path, api_key: &str, language: Option<&str>) -> Result<Transcript> {
+  let size = fs::metadata(wav)?.len();
+  if size > MAX_UPLOAD_BYTES {
+    return Err(anyhow!(
+      "recording is {:.1} MB but OpenAI accepts at most 25 MB; use the local backend \
+       or split the session",
+      size as f64 / (1024.0 * 1024.0)
+    ));
+  }
+
+  let settings = load_settings();
+  let retries = settings.openai_max_retries.unwrap_or(DEFAULT_MAX_RETRIES);
+  let base = Duration::from_millis(settings.openai_retry_base_ms.unwrap_or(DEFAULT_RETRY_BASE_MS));
+  let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+  let mut attempt = 0;
+  let sent = loop {
+    // A form is consumed by sending it, so each attempt builds its own.
+    let sent = client
+      .post(TRANSCRIPTIONS_URL)
+      .bearer_auth(api_key)
+      .multipart(transcription_form(wav, language)?)
+      .send();
+    let transient = match &sent {
+      Ok(r) => matches!(r.status().as_u16(), 429 | 500..=599),
+      Err(_) => true,
+    };
+    if !transient || attempt >= retries {
+      break sent;
+    }
+    let backoff = base.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_RETRY_DELAY);
+    let delay = sent.as_ref().ok().and_then(retry_after).unwrap_or(backoff);
+    tracing::warn!(
+      "OpenAI transcription attempt {} failed ({}); retrying in {:.1}s",
+      attempt + 1,
+      sent.as_ref().map_or("no response".into(), |r| format!("HTTP {}", r.status().as_u16())),
+      delay.as_secs_f64()
+    );
+    thread::sleep(delay);
+    attempt += 1;
+  };
+
+  // After retries, say how many were made and what the last one got back.
+  let gave_up = |last: &str, err: anyhow::Error| match attempt {
+    0 => err,
+    n => anyhow!("OpenAI transcription failed after {} attempts (last: {last}): {err}", n + 1),
+  };
+  let resp = sent.map_err(|e| {
+    gave_up("no response", anyhow!("could not reach OpenAI: {}", e.without_url()))
+  })?;
+  let status = resp.status().as_u16();
+  if status == 413 {
+    return Err(anyhow!("recording is too large for OpenAI (25 MB limit)"));
+  }
+  if !resp.status().is_success() {
+    return Err(gave_up(&format!("HTTP {status}"), api_error(resp, "transcription")));
+  }
+
+  let body: VerboseTranscription = resp.json()?;
+  let segments = body
+    .segments
+    .into_iter()
+    .map(|s| Segment {
+      start: s.start,
+      end: s.end,
+      text: s.text.trim().to_string(),
+    })
+    .filter(|s| !s.text.is_empty())
+    .collect();
+  // The API reports "english"; normalise to the code the local backend uses.
+  let language = match language {
+    Some(code) => Some(code.to_string()),
+    None => parse_language(body.language.as_deref()).ok().flatten(),
+  };
+  Ok(Transcript {
+    text: body.text.trim().to_string(),
+    segments,
+    language,
+    cancelled: false,
+  })
+}
@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{
+  ffi::OsStr,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use crate::sessions::{reserve_stem, sanitize_name};
+use crate::tools::{require_tool, run_tool};
+
+// Written between pages of the extracted text, like pdftotext does.
+const PAGE_BREAK: &str = "\n\u{0C}\n";
+// Below this many non-whitespace characters per page the PDF is treated as
+// a scan: digital handouts have hundreds.
+const MIN_CHARS_PER_PAGE: usize = 20;
+const OCR_DPI: &str = "300";
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractMethod {
+  Text,
+  Ocr,
+}
+
+/// Result of `import_pdf_file_cmd`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfImport {
+  pub text_path: String,
+  pub page_count: usize,
+  pub char_count: usize,
+  pub method: ExtractMethod,
+}
+
+/// Extracts the text of every page and writes it to `<out_dir>/<name>.txt`,
+/// pages separated by form feeds. Password-protected PDFs are refused. With
+/// `allow_ocr`, a PDF that is mostly images is OCR'd with tesseract instead.
+pub fn import_pdf(path: &Path, out_dir: &Path, allow_ocr: bool) -> Result<PdfImport> {
+  if !path.is_file() {
+    return Err(anyhow!("file not found: {}", path.display()));
+  }
+  let is_pdf = path
+    .extension()
+    .is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+  if !is_pdf {
+    return Err(anyhow!("not a PDF: {}", path.display()));
+  }
+
+  let bytes = fs::read(path)?;
+  let mut doc = pdf_extract::Document::load_mem(&bytes)
+    .map_err(|e| anyhow!("could not read PDF: {e}"))?;
+  // Many "encrypted" PDFs only restrict printing and open with an empty
+  // password; anything else needs the user to unlock it first.
+  if doc.is_encrypted() && doc.decrypt("").is_err() {
+    return Err(anyhow!(
+      "PDF is password-protected; remove the password and import it again"
+    ));
+  }
+  let pages = pdf_extract::extract_text_from_mem_by_pages(&bytes)
+    .map_err(|e| anyhow!("could not extract text from PDF: {e}"))?;
+
+  let mut page_count = pages.len();
+  let mut text = join_pages(&pages);
+  let mut method = ExtractMethod::Text;
+  if allow_ocr && count_chars(&text) < MIN_CHARS_PER_PAGE * page_count.max(1) {
+    let ocr_pages = ocr_pages(path)?;
+    page_count = ocr_pages.len();
+    text = join_pages(&ocr_pages);
+    method = ExtractMethod::Ocr;
+  }
+
+  fs::create_dir_all(out_dir)?;
+  let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("document");
+  let base = sanitize_name(stem).unwrap_or_else(|_| "document".into());
+  let reserved = reserve_stem(out_dir, &base)?;
+  let text_path = out_dir.join(format!("{}.txt", reserved.id));
+  fs::write(&text_path, &text)?;
+
+  Ok(PdfImport {
+    text_path: text_path.to_string_lossy().to_string(),
+    page_count,
+    char_count: count_chars(&text),
+    method,
+  })
+}
+
+fn join_pages(pages: &[String]) -> String {
+  pages
+    .iter()
+    .map(|p| p.trim())
+    .collect::<Vec<_>>()
+    .join(PAGE_BREAK)
+}
+
+fn count_chars(text: &str) -> usize {
+  text.chars().filter(|c| !c.is_whitespace()).count()
+}
+
+// Renders each page to a PNG with poppler's pdftoppm, then runs tesseract
+// on each image. Slow: a few seconds per page.
+fn ocr_pages(pdf: &Path) -> Result<Vec<String>> {
+  let pdftoppm = require_tool("pdftoppm", "install poppler to OCR scanned PDFs")?;
+  let tesseract = require_tool("tesseract", "install Tesseract OCR to OCR scanned PDFs")?;
+
+  let work = std::env::temp_dir().join(format!(
+    "applesauce-ocr-{}",
+    chrono::Local::now().timestamp_millis()
+  ));
+  fs::create_dir_all(&work)?;
+  let result = (|| {
+    let prefix = work.join("page");
+    let flags = ["-r", OCR_DPI, "-gray", "-png"].map(OsStr::new);
+    run_tool(&pdftoppm, flags.iter().copied().chain([pdf.as_os_str(), prefix.as_os_str()]))?;
+    // pdftoppm zero-pads page numbers, so name order is page order.
+    let mut images: Vec<PathBuf> = fs::read_dir(&work)?
+      .flatten()
+      .map(|e| e.path())
+      .filter(|p| p.extension().is_some_and(|e| e == "png"))
+      .collect();
+    images.sort();
+    images
+      .iter()
+      .map(|img| {
+        let out = run_tool(&tesseract, [img.as_os_str(), OsStr::new("stdout")])?;
+        Ok(String::from_utf8_lossy(&out).to_string())
+      })
+      .collect::<Result<Vec<_>>>()
+  })();
+  fs::remove_dir_all(&work).ok();
+  result
+}
@@ -0,0 +1,73 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path, time::UNIX_EPOCH};
+
+use crate::audio::{load_for_whisper, read_wav_f32};
+use crate::sessions::{find_recording, read_json_or_default, write_json_atomic};
+
+pub const DEFAULT_BUCKETS: usize = 1_000;
+const MAX_BUCKETS: usize = 20_000;
+
+// `<id>.peaks.json`. Keyed on the recording's size and mtime so edits like
+// normalize or trim-in-place invalidate it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PeaksCache {
+  source_len: u64,
+  source_mtime_ms: u128,
+  buckets: usize,
+  peaks: Vec<f32>,
+}
+
+/// `buckets` (min, max) pairs, flattened as `[min0, max0, min1, max1, ...]`.
+/// Channels are merged: each bucket spans all of them.
+pub fn waveform_peaks(dir: &Path, session_id: &str, buckets: usize) -> Result<Vec<f32>> {
+  let buckets = buckets.clamp(1, MAX_BUCKETS);
+  let path = find_recording(dir, session_id)
+    .ok_or_else(|| anyhow!("no recording found for session '{session_id}'"))?;
+  let meta = fs::metadata(&path)?;
+  let source_mtime_ms = meta
+    .modified()
+    .ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map_or(0, |d| d.as_millis());
+
+  let cache_path = dir.join(format!("{session_id}.peaks.json"));
+  let cached: PeaksCache = read_json_or_default(&cache_path);
+  let fresh = cached.source_len == meta.len() && cached.source_mtime_ms == source_mtime_ms;
+  if fresh && cached.buckets == buckets {
+    return Ok(cached.peaks);
+  }
+
+  // Channel layout doesn't matter once merged; compressed recordings come
+  // back from ffmpeg as mono.
+  let samples = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("wav")) {
+    read_wav_f32(&path)?.1
+  } else {
+    load_for_whisper(&path)?
+  };
+  let peaks = bucket_peaks(&samples, buckets);
+  let cache = PeaksCache {
+    source_len: meta.len(),
+    source_mtime_ms,
+    buckets,
+    peaks,
+  };
+  if let Err(e) = write_json_atomic(&cache_path, &cache) {
+    tracing::warn!("caching peaks for {session_id} failed: {e}");
+  }
+  Ok(cache.peaks)
+}
+
+fn bucket_peaks(samples: &[f32], buckets: usize) -> Vec<f32> {
+  let mut out = Vec::with_capacity(buckets * 2);
+  for b in 0..buckets {
+    let start = samples.len() * b / buckets;
+    let end = samples.len() * (b + 1) / buckets;
+    let (min, max) = samples[start..end]
+      .iter()
+      .fold((0.0_f32, 0.0_f32), |(lo, hi), &s| (lo.min(s), hi.max(s)));
+    out.push(min.max(-1.0));
+    out.push(max.min(1.0));
+  }
+  out
+}
@@ -0,0 +1,177 @@
+use anyhow::{anyhow, Result};
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender};
+use rodio::{Decoder, OutputStream, Sink, Source};
+use serde::Serialize;
+use std::{
+  fs::File,
+  io::BufReader,
+  path::{Path, PathBuf},
+  thread::{self, JoinHandle},
+  time::Duration,
+};
+use tauri::{AppHandle, Emitter};
+
+// How long play_session waits for the output stream to open.
+const START_TIMEOUT: Duration = Duration::from_secs(2);
+// Gap between `playback://position` events while playing.
+const POSITION_INTERVAL: Duration = Duration::from_millis(100);
+
+pub const POSITION_EVENT: &str = "playback://position";
+pub const ENDED_EVENT: &str = "playback://ended";
+
+#[derive(Debug, Clone, Copy)]
+enum PlaybackCmd {
+  Pause,
+  Resume,
+  Seek(f64),
+  Stop,
+}
+
+/// Payload of `playback://position`, so the transcript view can follow along.
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionEvent {
+  pub session_id: String,
+  pub secs: f64,
+  pub paused: bool,
+}
+
+/// What `play_session` hands back. `duration_secs` is None when the decoder
+/// can't tell up front (some MP3s).
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackStarted {
+  pub session_id: String,
+  pub duration_secs: Option<f64>,
+}
+
+/// The one playback at a time. Like the recorder, the rodio/CPAL objects
+/// live on their own thread and are driven over a channel.
+#[derive(Debug, Default)]
+pub struct PlayerState {
+  tx: Option<Sender<PlaybackCmd>>,
+  handle: Option<JoinHandle<()>>,
+}
+
+/// Starts playing `path` from `start_secs`, replacing whatever was playing.
+pub fn play_session(
+  state: &mut PlayerState,
+  app: AppHandle,
+  session_id: String,
+  path: PathBuf,
+  start_secs: f64,
+) -> Result<PlaybackStarted> {
+  stop_playback(state);
+  let (tx, rx) = unbounded::<PlaybackCmd>();
+  let (ready_tx, ready_rx) = bounded::<Result<Option<f64>>>(1);
+  let id = session_id.clone();
+  let handle = thread::Builder::new()
+    .name("playback".into())
+    .spawn(move || run_player(&app, &id, &path, start_secs, rx, ready_tx))?;
+  let duration_secs = match ready_rx.recv_timeout(START_TIMEOUT) {
+    Ok(Ok(duration)) => duration,
+    Ok(Err(e)) => {
+      handle.join().ok();
+      return Err(e);
+    }
+    Err(_) => {
+      // Dropping tx ends a thread that comes up late.
+      return Err(anyhow!("audio output did not start"));
+    }
+  };
+  state.tx = Some(tx);
+  state.handle = Some(handle);
+  Ok(PlaybackStarted { session_id, duration_secs })
+}
+
+pub fn pause_playback(state: &mut PlayerState) -> Result<()> {
+  send(state, PlaybackCmd::Pause)
+}
+
+pub fn resume_playback(state: &mut PlayerState) -> Result<()> {
+  send(state, PlaybackCmd::Resume)
+}
+
+pub fn seek_playback(state: &mut PlayerState, secs: f64) -> Result<()> {
+  send(state, PlaybackCmd::Seek(secs))
+}
+
+/// Stops and joins the player; a no-op when nothing is playing.
+pub fn stop_playback(state: &mut PlayerState) {
+  if let Some(tx) = state.tx.take() {
+    tx.send(PlaybackCmd::Stop).ok();
+  }
+  if let Some(h) = state.handle.take() {
+    h.join().ok();
+  }
+}
+
+fn send(state: &mut PlayerState, cmd: PlaybackCmd) -> Result<()> {
+  // A player that reached the end has exited; treat it as idle.
+  if state.handle.as_ref().is_some_and(|h| h.is_finished()) {
+    stop_playback(state);
+  }
+  let tx = state.tx.as_ref().ok_or_else(|| anyhow!("nothing is playing"))?;
+  tx.send(cmd).map_err(|e| anyhow!(e.to_string()))
+}
+
+fn open_source(path: &Path) -> Result<Decoder<BufReader<File>>> {
+  let file = File::open(path).map_err(|e| anyhow!("can't open {}: {e}", path.display()))?;
+  Decoder::new(BufReader::new(file)).map_err(|e| anyhow!("can't decode {}: {e}", path.display()))
+}
+
+fn run_player(
+  app: &AppHandle,
+  session_id: &str,
+  path: &Path,
+  start_secs: f64,
+  rx: Receiver<PlaybackCmd>,
+  ready_tx: Sender<Result<Option<f64>>>,
+) {
+  let opened = (|| {
+    let source = open_source(path)?;
+    let duration = source.total_duration().map(|d| d.as_secs_f64());
+    let (stream, handle) = OutputStream::try_default()?;
+    let sink = Sink::try_new(&handle)?;
+    sink.append(source);
+    if start_secs > 0.0 {
+      sink
+        .try_seek(Duration::from_secs_f64(start_secs))
+        .map_err(|e| anyhow!("can't seek: {e}"))?;
+    }
+    Ok::<_, anyhow::Error>((stream, sink, duration))
+  })();
+  // The stream has to stay alive for as long as the sink plays.
+  let (_stream, sink) = match opened {
+    Ok((stream, sink, duration)) => {
+      ready_tx.send(Ok(duration)).ok();
+      (stream, sink)
+    }
+    Err(e) => {
+      ready_tx.send(Err(e)).ok();
+      return;
+    }
+  };
+
+  loop {
+    match rx.recv_timeout(POSITION_INTERVAL) {
+      Ok(PlaybackCmd::Pause) => sink.pause(),
+      Ok(PlaybackCmd::Resume) => sink.play(),
+      Ok(PlaybackCmd::Seek(secs)) => {
+        if let Err(e) = sink.try_seek(Duration::from_secs_f64(secs.max(0.0))) {
+          tracing::warn!("seek to {secs:.1}s failed: {e}");
+        }
+      }
+      Ok(PlaybackCmd::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+      Err(RecvTimeoutError::Timeout) => {}
+    }
+    if sink.empty() {
+      app.emit(ENDED_EVENT, session_id).ok();
+      break;
+    }
+    let position = PositionEvent {
+      session_id: session_id.to_string(),
+      secs: sink.get_pos().as_secs_f64(),
+      paused: sink.is_paused(),
+    };
+    app.emit(POSITION_EVENT, position).ok();
+  }
+}
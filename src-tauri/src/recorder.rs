@@ -1,30 +1,609 @@
 use anyhow::{anyhow, Result};
-use crossbeam_channel::{unbounded, Receiver, Sender};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{FromSample, Sample, SizedSample};
+use crossbeam_channel::{
+  bounded, unbounded, Receiver, RecvTimeoutError, Select, Sender, TryRecvError,
+};
 use hound::{SampleFormat, WavSpec, WavWriter};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::audio::{
+  repair_wav_header, resample_linear, to_mono, validate_wav, write_sample, FrameResampler,
+};
+use crate::devices::{find_input_device, find_loopback_device, input_device_present};
+use crate::imports::register_saved_audio;
+use crate::tools::require_tool;
+use crate::transcribe::LiveTranscriber;
+use crate::sessions::{
+  find_recording, is_recording, is_reserved, read_meta, reserve_stem, sanitize_name,
+  validate_session_id, write_meta, Marker, ReservedStem, SessionMeta,
+};
+use crate::settings::{load_settings, remember_recording};
+use crate::storage::free_space;
 use std::{
-  fs,
+  collections::VecDeque,
+  fmt, fs,
+  io::{BufWriter, Write},
   path::{Path, PathBuf},
-  thread,
-  time::SystemTime,
+  process::{Child, ChildStdin, Command, Stdio},
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+  },
+  thread::{self, JoinHandle},
+  time::{Duration, Instant, SystemTime},
 };
 
-#[derive(Debug, Clone, Copy)]
+// How long stop_recording waits for the thread to finalize the WAV.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+// How long start_recording waits for the stream to come up before returning.
+const START_TIMEOUT: Duration = Duration::from_secs(2);
+// Level meter block size and the minimum gap between emitted events.
+const LEVEL_INTERVAL: Duration = Duration::from_millis(50);
+// Free space needed on the storage volume to start, and the floor below
+// which a running recording stops itself so the file is finalized intact.
+const MIN_FREE_TO_START: u64 = 200 * 1024 * 1024;
+const MIN_FREE_WHILE_RECORDING: u64 = 50 * 1024 * 1024;
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// How often the mic is looked for in the device list. Not every backend
+// reports an unplugged device as a stream error, so this catches the rest.
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+// Longest the audio thread sleeps with nothing arriving, so the disk check
+// still runs while a device delivers nothing.
+const IDLE_WAKE: Duration = Duration::from_millis(250);
+// A mic test is a quick check, not a recording.
+const MAX_MIC_TEST_SECS: f64 = 10.0;
+// Peak under this (about -60 dBFS) counts as no signal at all.
+const SIGNAL_FLOOR: f32 = 0.001;
+
+pub const LEVEL_EVENT: &str = "recording://level";
+pub const AUTO_STOPPED_EVENT: &str = "recording://auto-stopped";
+pub const DEVICE_LOST_EVENT: &str = "recording://device-lost";
+pub const CLIPPING_EVENT: &str = "recording://clipping-warning";
+pub const COUNTDOWN_EVENT: &str = "recording://countdown";
+pub const TICK_EVENT: &str = "recording://tick";
+pub const STATE_EVENT: &str = "recording://state";
+// Gap between `recording://tick` events.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+// A sample at or above this (of full scale) counts as clipped; converted
+// int samples rarely land exactly on 1.0.
+const CLIP_LEVEL: f32 = 0.99;
+// Percent of clipped samples past which the UI is told to suggest less gain.
+const CLIPPING_WARN_PCT: f32 = 0.1;
+
+#[derive(Debug, Clone)]
 pub enum Cmd {
   Pause,
   Resume,
   Stop,
+  // Stop and throw away what this run recorded.
+  Cancel,
+  // Bookmark the current position, with an optional label.
+  Marker(Option<String>),
+  // New input gain in dB, already clamped.
+  SetGain(f32),
+}
+
+/// Range `input_gain_db` is clamped to.
+pub const MIN_INPUT_GAIN_DB: f32 = -20.0;
+pub const MAX_INPUT_GAIN_DB: f32 = 20.0;
+
+fn clamp_gain_db(db: f32) -> f32 {
+  if db.is_finite() {
+    db.clamp(MIN_INPUT_GAIN_DB, MAX_INPUT_GAIN_DB)
+  } else {
+    0.0
+  }
+}
+
+fn db_to_linear(db: f32) -> f32 {
+  10f32.powf(db / 20.0)
+}
+
+// Bottom of the meter's dB scale; quieter (including silence) reads as this.
+const METER_FLOOR_DB: f32 = -60.0;
+
+// dBFS of a 0.0..=1.0 level, clamped to METER_FLOOR_DB..=0.
+fn linear_to_db(level: f32) -> f32 {
+  if level <= 0.0 {
+    return METER_FLOOR_DB;
+  }
+  (20.0 * level.log10()).clamp(METER_FLOOR_DB, 0.0)
+}
+
+/// What the recording is saved as. WAV is written in-process; MP3 (roughly a
+/// tenth of the size) and FLAC (lossless, about half) are encoded by an
+/// ffmpeg child as the audio comes in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+  #[default]
+  Wav,
+  Mp3,
+  Flac,
+}
+
+impl OutputFormat {
+  pub fn extension(&self) -> &'static str {
+    match self {
+      OutputFormat::Wav => "wav",
+      OutputFormat::Mp3 => "mp3",
+      OutputFormat::Flac => "flac",
+    }
+  }
+
+  // ffmpeg encoder arguments; None for formats written in-process.
+  fn ffmpeg_codec(&self) -> Option<&'static [&'static str]> {
+    match self {
+      OutputFormat::Wav => None,
+      OutputFormat::Mp3 => Some(&["-c:a", "libmp3lame", "-q:a", "5"]),
+      OutputFormat::Flac => Some(&["-c:a", "flac"]),
+    }
+  }
+}
+
+/// What gets recorded. `System` is whatever the computer is playing (a Zoom
+/// call, a lecture video); `Both` mixes it into the microphone. System audio
+/// needs WASAPI loopback, so it only works on Windows (see
+/// `find_loopback_device`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureSource {
+  #[default]
+  Mic,
+  System,
+  Both,
+}
+
+/// Caller-requested capture format. Fields left out of the JSON fall back to
+/// Whisper-friendly 16 kHz / mono / 16-bit. The device may not support what
+/// was asked for, in which case the nearest supported config is used and
+/// reported back in `StartResponse`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+  pub sample_rate: u32,
+  pub channels: u16,
+  // 16 or 24 (PCM int) or 32 (float); anything else snaps to 16. MP3 and
+  // FLAC are always fed 16-bit.
+  pub bits_per_sample: u16,
+  pub format: OutputFormat,
+  // `System` ignores sample_rate/channels: loopback captures the output
+  // device's own mix format.
+  pub source: CaptureSource,
+  // Stop by itself after this much recorded (unpaused) audio. None = no cap.
+  pub max_duration_secs: Option<f64>,
+  // Stop by itself after this long below `silence_threshold`. None = off.
+  pub silence_timeout_secs: Option<f64>,
+  // Block RMS (0.0..=1.0 of full scale) under which audio counts as quiet.
+  pub silence_threshold: f32,
+  // Software gain on the input before it is written, clamped to -20..=20 dB.
+  // Loud samples are clipped to full scale rather than wrapped.
+  pub input_gain_db: f32,
+  // Noise gate: once the mic's RMS stays under this (0.0..=1.0) for a short
+  // hold, it is turned down by `gate_attenuation_db` until speech returns.
+  // None = off.
+  pub gate_threshold: Option<f32>,
+  pub gate_attenuation_db: f32,
+  // Seconds of `recording://countdown` ticks before the stream opens. The
+  // file and its start time begin after the countdown. 0 = start at once.
+  pub preroll_secs: u32,
+  // Average the device's channels into a mono file, for devices that only
+  // capture in stereo. `channels` then only picks what the device opens with.
+  pub downmix_to_mono: bool,
+  // Write the file at this rate, resampling as audio comes in, when the
+  // device can't capture at `sample_rate` itself (e.g. 48 kHz only, but
+  // Whisper wants 16 kHz). None = the device's rate.
+  pub resample_to: Option<u32>,
+  // Also feed what's recorded to Whisper and emit `transcribe://live`
+  // results as it goes. Needs the default model downloaded; without it the
+  // recording goes ahead without live text.
+  pub live_transcribe: bool,
+}
+
+impl Default for RecordingConfig {
+  fn default() -> Self {
+    Self {
+      sample_rate: 16_000,
+      channels: 1,
+      bits_per_sample: 16,
+      format: OutputFormat::Wav,
+      source: CaptureSource::Mic,
+      max_duration_secs: None,
+      silence_timeout_secs: None,
+      silence_threshold: 0.01,
+      input_gain_db: 0.0,
+      gate_threshold: None,
+      gate_attenuation_db: -60.0,
+      preroll_secs: 0,
+      downmix_to_mono: false,
+      resample_to: None,
+      live_transcribe: false,
+    }
+  }
+}
+
+/// Broad failure categories so the frontend can tell a missing/unplugged mic
+/// from a full or unwritable disk. They show up as a `[kind]` prefix on the
+/// command error string, e.g. `[device] input device disconnected at 12.3s`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecorderErrorKind {
+  Device,
+  Disk,
+  Other,
+}
+
+impl RecorderErrorKind {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      RecorderErrorKind::Device => "device",
+      RecorderErrorKind::Disk => "disk",
+      RecorderErrorKind::Other => "other",
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecorderError {
+  pub kind: RecorderErrorKind,
+  pub message: String,
+}
+
+impl RecorderError {
+  fn device(e: impl fmt::Display) -> Self {
+    Self { kind: RecorderErrorKind::Device, message: e.to_string() }
+  }
+
+  fn disk(e: impl fmt::Display) -> Self {
+    Self { kind: RecorderErrorKind::Disk, message: e.to_string() }
+  }
+
+  fn other(e: impl fmt::Display) -> Self {
+    Self { kind: RecorderErrorKind::Other, message: e.to_string() }
+  }
+}
+
+impl fmt::Display for RecorderError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "[{}] {}", self.kind.as_str(), self.message)
+  }
+}
+
+impl std::error::Error for RecorderError {}
+
+/// Payload of `recording://level`. `rms`/`peak` are 0.0..=1.0 of full
+/// scale; the `_db` pair is the same in dBFS, from -60 (or silence) to 0.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LevelEvent {
+  pub rms: f32,
+  pub peak: f32,
+  pub rms_db: f32,
+  pub peak_db: f32,
+}
+
+// Accumulates ~LEVEL_INTERVAL worth of samples and yields one reading per
+// block. `due` rate-limits on wall clock for emitting, since a backlog
+// drained in one pass would otherwise fire a burst of events.
+struct LevelMeter {
+  block_len: usize,
+  count: usize,
+  sum_sq: f64,
+  peak: f32,
+  last_emit: Option<Instant>,
+}
+
+impl LevelMeter {
+  fn new(sample_rate: u32, channels: u16) -> Self {
+    let per_sec = sample_rate as usize * channels.max(1) as usize;
+    Self {
+      block_len: (per_sec * LEVEL_INTERVAL.as_millis() as usize / 1000).max(1),
+      count: 0,
+      sum_sq: 0.0,
+      peak: 0.0,
+      last_emit: None,
+    }
+  }
+
+  fn push(&mut self, s: f32) -> Option<LevelEvent> {
+    self.sum_sq += (s as f64) * (s as f64);
+    self.peak = self.peak.max(s.abs());
+    self.count += 1;
+    if self.count < self.block_len {
+      return None;
+    }
+    let rms = (self.sum_sq / self.count as f64).sqrt() as f32;
+    let peak = self.peak.min(1.0);
+    let reading = LevelEvent { rms, peak, rms_db: linear_to_db(rms), peak_db: linear_to_db(peak) };
+    self.count = 0;
+    self.sum_sq = 0.0;
+    self.peak = 0.0;
+    Some(reading)
+  }
+
+  fn due(&mut self) -> bool {
+    let due = self.last_emit.is_none_or(|t| t.elapsed() >= LEVEL_INTERVAL);
+    if due {
+      self.last_emit = Some(Instant::now());
+    }
+    due
+  }
+}
+
+// Noise gate timings. The window is short so the gate reacts within a
+// syllable; the hold keeps it from chattering in the gaps between words.
+const GATE_WINDOW: Duration = Duration::from_millis(10);
+const GATE_HOLD: Duration = Duration::from_millis(300);
+const GATE_ATTACK: Duration = Duration::from_millis(5);
+const GATE_RELEASE: Duration = Duration::from_millis(80);
+
+// Turns the mic down while it's only hiss. RMS is measured over short
+// windows of what already went by, and the gain glides toward its target
+// (fast to open, slower to close) so there are no clicks.
+struct NoiseGate {
+  threshold: f32,
+  floor: f32,
+  window_len: usize,
+  hold_len: usize,
+  count: usize,
+  sum_sq: f64,
+  quiet_len: usize,
+  gain: f32,
+  target: f32,
+  attack: f32,
+  release: f32,
+}
+
+impl NoiseGate {
+  fn new(threshold: f32, attenuation_db: f32, sample_rate: u32, channels: u16) -> Self {
+    let per_sec = sample_rate as f32 * channels.max(1) as f32;
+    let len = |d: Duration| ((per_sec * d.as_secs_f32()) as usize).max(1);
+    // One-pole smoothing coefficient for a time constant of `d`.
+    let coeff = |d: Duration| 1.0 - (-1.0 / (per_sec * d.as_secs_f32()).max(1.0)).exp();
+    Self {
+      threshold,
+      floor: db_to_linear(attenuation_db.min(0.0)),
+      window_len: len(GATE_WINDOW),
+      hold_len: len(GATE_HOLD),
+      count: 0,
+      sum_sq: 0.0,
+      quiet_len: 0,
+      gain: 1.0,
+      target: 1.0,
+      attack: coeff(GATE_ATTACK),
+      release: coeff(GATE_RELEASE),
+    }
+  }
+
+  fn process(&mut self, s: f32) -> f32 {
+    self.sum_sq += (s as f64) * (s as f64);
+    self.count += 1;
+    if self.count >= self.window_len {
+      let rms = (self.sum_sq / self.count as f64).sqrt() as f32;
+      if rms >= self.threshold {
+        self.quiet_len = 0;
+        self.target = 1.0;
+      } else {
+        self.quiet_len += self.count;
+        if self.quiet_len >= self.hold_len {
+          self.target = self.floor;
+        }
+      }
+      self.count = 0;
+      self.sum_sq = 0.0;
+    }
+    let coeff = if self.target > self.gain { self.attack } else { self.release };
+    self.gain += (self.target - self.gain) * coeff;
+    s * self.gain
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoStopReason {
+  MaxDuration,
+  Silence,
+  LowDisk,
+}
+
+/// Payload of `recording://device-lost`, sent as soon as the input device
+/// goes away. The recording then ends with a `[device]` error from
+/// stop_recording, and what was captured up to `at_secs` is kept.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceLostEvent {
+  pub session_id: String,
+  pub device_name: Option<String>,
+  pub at_secs: f64,
+}
+
+/// Payload of `recording://tick`, for the running timer. `elapsed_secs` is
+/// counted from the frames written, so it matches the final file's length.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TickEvent {
+  pub elapsed_secs: f64,
+  pub paused: bool,
+}
+
+/// Payload of `recording://countdown`, once a second during the pre-roll
+/// (`remaining_secs` counts down to 1; recording starts a second later).
+#[derive(Debug, Clone, Serialize)]
+pub struct CountdownEvent {
+  pub session_id: String,
+  pub remaining_secs: u32,
+}
+
+/// Payload of `recording://clipping-warning`, emitted after a recording that
+/// clipped more than a little, so the UI can suggest lowering input gain.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClippingWarningEvent {
+  pub session_id: String,
+  pub clipping_pct: f32,
+}
+
+/// Payload of `recording://auto-stopped`, emitted once the file is finalized.
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoStopEvent {
+  pub reason: AutoStopReason,
+  pub path: String,
+  pub duration_secs: f64,
+}
+
+// Everything the audio thread needs, moved in at spawn.
+struct AudioJob {
+  session_id: String,
+  // Where the audio is written while recording. New sessions go under
+  // `.tmp` in the storage folder; resumed ones append in place.
+  path: PathBuf,
+  // `<id>.wav`, `.mp3` or `.flac` in the storage folder, per the requested
+  // format. The file is moved here once finalized.
+  final_path: PathBuf,
+  // As requested (None = default); used to open the device.
+  device_name: Option<String>,
+  // Resolved name, recorded in the session metadata.
+  device_label: Option<String>,
+  config: Option<RecordingConfig>,
+  started_at: String,
+  // Set when resuming: the file is appended to and this metadata extended.
+  existing: Option<SessionMeta>,
+  // Stable id kept in the metadata; see SessionMeta::uid.
+  uid: Option<String>,
+  // A new session's id, held until its file has moved out of `.tmp`; the
+  // thread drops it with the job when it's done.
+  reserved: Option<ReservedStem>,
+  // Opens the capture once any countdown is over: `open_device`, or a
+  // scripted source in tests.
+  open_source: OpenSource,
+  // None in tests, where events go nowhere.
+  app: Option<AppHandle>,
+  elapsed_ms: Arc<AtomicU64>,
+}
+
+// Builds a job's capture source. Runs on the audio thread, since CPAL
+// streams can't leave it; stream errors go to the given sender.
+type OpenSource = Box<
+  dyn Fn(&AudioJob, Sender<cpal::StreamError>) -> RecorderResult<Box<dyn SampleSource>> + Send,
+>;
+
+impl AudioJob {
+  fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+    if let Some(app) = &self.app {
+      app.emit(event, payload).ok();
+    }
+  }
+}
+
+// Sent once by the audio thread after the file is finalized.
+#[derive(Debug, Clone, Copy)]
+struct ThreadReport {
+  // Frames (one sample per channel) in the file; paused time excluded. For a
+  // resumed session this includes what was there before.
+  frames_written: u64,
+  sample_rate: u32,
+  // Percent of written samples at or near full scale.
+  clipping_pct: f32,
+  // Stopped during the pre-roll, before any file was opened, or cancelled
+  // and its audio discarded.
+  cancelled: bool,
 }
 
+impl ThreadReport {
+  fn duration_secs(&self) -> f64 {
+    if self.sample_rate == 0 {
+      return 0.0;
+    }
+    self.frames_written as f64 / self.sample_rate as f64
+  }
+}
+
+type RecorderResult<T> = std::result::Result<T, RecorderError>;
+type ThreadResult = RecorderResult<ThreadReport>;
+
 #[derive(Debug)]
 pub struct RecorderState {
   // Control to the recording thread (if any).
   tx: Option<Sender<Cmd>>,
+  // ACK from the recording thread once it has finalized.
+  ack_rx: Option<Receiver<ThreadResult>>,
+  // Joined on stop so callers only see the file after finalize.
+  handle: Option<JoinHandle<()>>,
   // Simple session bookkeeping.
   session_id: Option<String>,
-  // Where the last WAV landed (for stop response).
-  last_wav: Option<PathBuf>,
+  // Where the last recording landed (for stop response).
+  last_path: Option<PathBuf>,
   // Are we currently paused? (mirrors the thread’s state)
   paused: bool,
+  // Result of a thread that stopped by itself, waiting for stop_recording.
+  finished: Option<ThreadResult>,
+  // Name of the input device the current/last session records from.
+  device_name: Option<String>,
+  // What the current session's stream opened with, for repeat starts.
+  config: Option<RecordingConfig>,
+  // Milliseconds of audio written so far, updated by the audio thread.
+  elapsed_ms: Arc<AtomicU64>,
+  // Set while test_microphone has a device open; see `MicTest`.
+  mic_test: Arc<AtomicBool>,
+}
+
+/// Snapshot for `recording_status_cmd`, so a reloaded UI can resync.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecorderStatus {
+  pub active: bool,
+  pub paused: bool,
+  pub session_id: Option<String>,
+  pub elapsed_secs: f64,
+}
+
+/// Payload of `recording://state`, sent after every start, pause, resume and
+/// stop so the UI has one thing to follow. `state` is "recording", "paused",
+/// "stopped" (the session that just ended, with its final length) or "idle".
+#[derive(Debug, Clone, Serialize)]
+pub struct StateEvent {
+  pub state: &'static str,
+  pub session_id: Option<String>,
+  pub elapsed_secs: f64,
+}
+
+/// Emits `recording://state` for where the recorder is now. Commands pass
+/// `stopped` right after they ended a recording, so the event names it.
+pub fn emit_state(app: &AppHandle, state: &mut RecorderState, stopped: bool) {
+  let status = recording_status(state);
+  let event = match (stopped, status.active, status.paused) {
+    (true, false, _) => StateEvent {
+      state: "stopped",
+      session_id: state.session_id.clone(),
+      elapsed_secs: state.elapsed_ms.load(Ordering::Relaxed) as f64 / 1000.0,
+    },
+    (_, true, paused) => StateEvent {
+      state: if paused { "paused" } else { "recording" },
+      session_id: status.session_id,
+      elapsed_secs: status.elapsed_secs,
+    },
+    _ => StateEvent { state: "idle", session_id: None, elapsed_secs: 0.0 },
+  };
+  app.emit(STATE_EVENT, event).ok();
+}
+
+/// What `start_recording` hands back to the command layer. `config` is what
+/// the device actually opened with, or `None` if it was still coming up when
+/// we stopped waiting. `already_running` means nothing new was started and
+/// this is the session that was already recording.
+#[derive(Debug, Clone)]
+pub struct StartedRecording {
+  pub session_id: String,
+  pub config: Option<RecordingConfig>,
+  pub already_running: bool,
+}
+
+/// What `stop_recording` hands back to the command layer. `path` is None
+/// when nothing was saved: stopped during its countdown, or discarded as
+/// shorter than settings.min_duration_secs.
+#[derive(Debug, Clone)]
+pub struct StoppedRecording {
+  pub path: Option<PathBuf>,
+  pub device_name: Option<String>,
+  pub duration_secs: f64,
+  pub clipping_pct: f32,
 }
 
 // Public API expected by main.rs
@@ -32,17 +611,33 @@ impl RecorderState {
   pub fn new() -> Self {
     Self {
       tx: None,
+      ack_rx: None,
+      handle: None,
       session_id: None,
-      last_wav: None,
+      last_path: None,
       paused: false,
+      finished: None,
+      device_name: None,
+      config: None,
+      elapsed_ms: Arc::new(AtomicU64::new(0)),
+      mic_test: Arc::new(AtomicBool::new(false)),
     }
   }
 }
 
-/// Returns our recording storage directory:
+/// Returns our recording storage directory: the folder chosen in settings
+/// (`set_storage_dir_cmd`), or `default_storage_dir()` when none is set.
+pub fn storage_dir() -> PathBuf {
+  match load_settings().storage_dir {
+    Some(dir) if !dir.trim().is_empty() => PathBuf::from(dir),
+    _ => default_storage_dir(),
+  }
+}
+
+/// The built-in location:
 /// - Prefer the OS-native Downloads folder: <Downloads>/ApplesauceCacheNative
 /// - Fallback to a temp dir if Downloads is unavailable
-pub fn storage_dir() -> PathBuf {
+pub fn default_storage_dir() -> PathBuf {
   if let Some(mut d) = dirs::download_dir() {
     d.push("ApplesauceCacheNative");
     return d;
@@ -53,42 +648,350 @@ pub fn storage_dir() -> PathBuf {
   d
 }
 
+/// Subfolder of the storage folder that new recordings are written into
+/// until they are finalized.
+pub const TEMP_DIR: &str = ".tmp";
+
+/// What `sweep_temp_recordings` did with each leftover: session ids that
+/// became sessions, and file names that were deleted.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TempSweep {
+  pub recovered: Vec<String>,
+  pub discarded: Vec<String>,
+}
+
+/// Deals with whatever is left in `.tmp` from recordings that never finished
+/// (the app was killed or crashed mid-recording). A WAV with audio in it gets
+/// its header fixed up and becomes a normal session, an MP3/FLAC is kept if
+/// it isn't empty, and everything else is deleted. One whose id is taken
+/// by then is kept under a suffixed id. A running recording's file is left
+/// alone, since its id is still reserved.
+pub fn sweep_temp_recordings(dir: &Path) -> Result<TempSweep> {
+  let tmp = dir.join(TEMP_DIR);
+  let mut sweep = TempSweep::default();
+  if !tmp.is_dir() {
+    return Ok(sweep);
+  }
+  for entry in fs::read_dir(&tmp)? {
+    let path = entry?.path();
+    // A recording that is still running holds its id until it leaves `.tmp`.
+    if path.file_stem().and_then(|s| s.to_str()).is_some_and(|id| is_reserved(dir, id)) {
+      continue;
+    }
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let id = match check_temp_file(&path) {
+      Ok(id) => id,
+      Err(e) => {
+        tracing::warn!("discarding unfinished recording {name}: {e}");
+        let removed = match path.is_dir() {
+          true => fs::remove_dir_all(&path),
+          false => fs::remove_file(&path),
+        };
+        if let Err(e) = removed {
+          tracing::warn!("removing {} failed: {e}", path.display());
+        }
+        sweep.discarded.push(name);
+        continue;
+      }
+    };
+    match recover_temp_file(dir, &path, &id) {
+      Ok(id) => {
+        tracing::info!("recovered unfinished recording {id}");
+        sweep.recovered.push(id);
+      }
+      // The audio is good; it stays in `.tmp` for the next sweep.
+      Err(e) => tracing::warn!("couldn't recover unfinished recording {name}: {e}"),
+    }
+  }
+  Ok(sweep)
+}
+
+// Whether a leftover is worth keeping: a recording with audio in it, its
+// WAV header fixed up. Returns its id; an error means it can go.
+fn check_temp_file(path: &Path) -> Result<String> {
+  let id = path
+    .file_stem()
+    .and_then(|s| s.to_str())
+    .filter(|_| is_recording(path))
+    .ok_or_else(|| anyhow!("not a recording"))?;
+  validate_session_id(id)?;
+  if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("wav")) {
+    repair_wav_header(path)?;
+    validate_wav(path)?;
+  } else if fs::metadata(path)?.len() == 0 {
+    return Err(anyhow!("file is empty"));
+  }
+  Ok(id.to_string())
+}
+
+// Moves a checked leftover into the storage folder as a session, under a
+// suffixed id if its own is taken by then. Returns the id used.
+fn recover_temp_file(dir: &Path, path: &Path, id: &str) -> Result<String> {
+  let reserved = reserve_stem(dir, id)?;
+  let ext = path.extension().unwrap_or_default().to_string_lossy();
+  let dest = dir.join(format!("{}.{ext}", reserved.id));
+  fs::rename(path, &dest)?;
+  // The audio is safe now; listing falls back to the header without a sidecar.
+  if let Err(e) = register_saved_audio(dir, &reserved.id, &dest) {
+    tracing::warn!("writing metadata for recovered {} failed: {e}", reserved.id);
+  }
+  Ok(reserved.id.clone())
+}
+
 // ---- high-level helpers called by Tauri commands ----
 
-pub fn start_recording(state: &mut RecorderState) -> Result<(String, PathBuf)> {
-  if state.tx.is_some() {
-    return Err(anyhow!("recording already in progress"));
+pub fn start_recording(
+  state: &mut RecorderState,
+  app: AppHandle,
+  device_name: Option<String>,
+  config: Option<RecordingConfig>,
+  label: Option<String>,
+) -> Result<StartedRecording> {
+  // A double-fired start (UI race, double click) gets the running session
+  // back instead of an error.
+  reap_finished(state);
+  if let (Some(_), Some(session_id)) = (&state.tx, &state.session_id) {
+    return Ok(StartedRecording {
+      session_id: session_id.clone(),
+      config: state.config.clone(),
+      already_running: true,
+    });
   }
+  ensure_idle(state)?;
+  // Whatever isn't given comes from the last recording. A remembered device
+  // that has since been unplugged falls back to the default.
+  let settings = load_settings();
+  let config = config.or(settings.last_recording_config);
+  let device_name = device_name.or_else(|| {
+    let host = cpal::default_host();
+    settings.last_device_name.filter(|n| input_device_present(&host, Some(n)))
+  });
+  let source = config.as_ref().map_or(CaptureSource::Mic, |c| c.source);
+  let resolved_name = resolve_device_label(device_name.as_deref(), source)?;
 
   // Ensure our target folder exists (Downloads/ApplesauceCacheNative).
   let dir = storage_dir();
   fs::create_dir_all(&dir)?;
 
-  let session_id = new_session_id();
-  let wav_path = dir.join(format!("{session_id}.wav"));
+  let uid = new_session_id();
+  // A label names the files (`Biology_Lecture_3.wav`); the generated id is
+  // still kept in the metadata.
+  let reserved = match label.as_deref().map(str::trim).filter(|l| !l.is_empty()) {
+    Some(label) => reserve_stem(&dir, &label_stem(label)?)?,
+    None => reserve_stem(&dir, &uid)?,
+  };
+  let session_id = reserved.id.clone();
+  let format = config.as_ref().map_or(OutputFormat::Wav, |c| c.format);
+  let file_name = format!("{session_id}.{}", format.extension());
+  // Recorded into `.tmp` so a half-written file never shows up as a session.
+  let tmp = dir.join(TEMP_DIR);
+  fs::create_dir_all(&tmp)?;
+
+  let job = AudioJob {
+    session_id,
+    path: tmp.join(&file_name),
+    final_path: dir.join(file_name),
+    device_name,
+    device_label: resolved_name,
+    config,
+    started_at: chrono::Local::now().to_rfc3339(),
+    existing: None,
+    reserved: Some(reserved),
+    uid: Some(uid),
+    open_source: Box::new(open_device),
+    app: Some(app),
+    elapsed_ms: Arc::new(AtomicU64::new(0)),
+  };
+  let remembered = (job.device_name.clone(), job.config.clone());
+  let started = launch(state, job)?;
+  if let Err(e) = remember_recording(remembered.0, remembered.1) {
+    tracing::warn!("saving the last recording config failed: {e}");
+  }
+  Ok(started)
+}
 
-  // Channel to control the audio thread
+/// Continues recording into an existing WAV session, e.g. after stopping by
+/// accident or a crash. New audio is appended to the same file with the
+/// file's own format, and the metadata (duration, markers, pauses) carries
+/// on from where it was. Only the capture source and auto-stop settings are
+/// taken from `config`.
+pub fn resume_session(
+  state: &mut RecorderState,
+  app: AppHandle,
+  session_id: &str,
+  device_name: Option<String>,
+  config: Option<RecordingConfig>,
+) -> Result<StartedRecording> {
+  ensure_idle(state)?;
+  validate_session_id(session_id)?;
+  let dir = storage_dir();
+  let path = find_recording(&dir, session_id)
+    .ok_or_else(|| anyhow!("session '{session_id}' not found"))?;
+  if !path.extension().is_some_and(|e| e.eq_ignore_ascii_case("wav")) {
+    return Err(anyhow!("only WAV recordings can be resumed"));
+  }
+  let reader = hound::WavReader::open(&path)?;
+  let spec = reader.spec();
+  drop(reader);
+
+  let config = RecordingConfig {
+    sample_rate: spec.sample_rate,
+    channels: spec.channels,
+    bits_per_sample: spec.bits_per_sample,
+    format: OutputFormat::Wav,
+    ..config.unwrap_or_default()
+  };
+  let resolved_name = resolve_device_label(device_name.as_deref(), config.source)?;
+  // Imports and old recordings may have no sidecar; start one from the file.
+  let existing = read_meta(&dir, session_id)
+    .unwrap_or_else(|| SessionMeta::new(session_id, spec.sample_rate, spec.channels));
+
+  let job = AudioJob {
+    session_id: session_id.to_string(),
+    path: path.clone(),
+    final_path: path,
+    device_name,
+    device_label: resolved_name.or_else(|| existing.device_name.clone()),
+    config: Some(config),
+    started_at: existing.started_at.clone(),
+    uid: existing.uid.clone(),
+    existing: Some(existing),
+    reserved: None,
+    open_source: Box::new(open_device),
+    app: Some(app),
+    elapsed_ms: Arc::new(AtomicU64::new(0)),
+  };
+  launch(state, job)
+}
+
+fn ensure_idle(state: &mut RecorderState) -> Result<()> {
+  reap_finished(state);
+  if state.tx.is_some() {
+    return Err(anyhow!("recording already in progress"));
+  }
+  if state.mic_test.load(Ordering::Relaxed) {
+    return Err(anyhow!("microphone test in progress; wait for it to finish"));
+  }
+  // An auto-stopped session nobody collected is superseded by this one.
+  state.finished = None;
+  Ok(())
+}
+
+// Resolves the device(s) up front so a bad name fails here, not silently in
+// the thread. The handles themselves are dropped; the thread opens its own.
+fn resolve_device_label(device_name: Option<&str>, source: CaptureSource) -> Result<Option<String>> {
+  let host = cpal::default_host();
+  let mic_name = || -> Result<Option<String>> {
+    Ok(find_input_device(&host, device_name)?.name().ok())
+  };
+  let system_name = || -> Result<Option<String>> { Ok(find_loopback_device(&host)?.name().ok()) };
+  Ok(match source {
+    CaptureSource::Mic => mic_name()?,
+    CaptureSource::System => system_name()?,
+    CaptureSource::Both => match (mic_name()?, system_name()?) {
+      (Some(mic), Some(system)) => Some(format!("{mic} + {system}")),
+      (mic, system) => mic.or(system),
+    },
+  })
+}
+
+// Spawns the audio thread for `job` and waits briefly for its stream to
+// come up.
+fn launch(state: &mut RecorderState, job: AudioJob) -> Result<StartedRecording> {
+  let session_id = job.session_id.clone();
+  let path = job.path.clone();
+  let final_path = job.final_path.clone();
+  let resolved_name = job.device_label.clone();
+  let elapsed_ms = job.elapsed_ms.clone();
+  let appending = job.existing.is_some();
+  let preroll = job.config.as_ref().map_or(0, |c| c.preroll_secs);
+
+  // A failed check isn't a reason to refuse; the writer reports real errors.
+  let free = path.parent().and_then(|dir| free_space(dir).ok());
+  if let Some(free) = free.filter(|&f| f < MIN_FREE_TO_START) {
+    return Err(
+      RecorderError::disk(format!(
+        "only {} MB free on the storage drive; free up space or choose another folder",
+        free / (1024 * 1024)
+      ))
+      .into(),
+    );
+  }
+
+  // Channel to control the audio thread, a one-shot "stream is up" signal,
+  // and the one-shot ACK carrying the final result.
   let (tx, rx) = unbounded::<Cmd>();
+  let (ready_tx, ready_rx) = bounded::<RecordingConfig>(1);
+  let (ack_tx, ack_rx) = bounded::<ThreadResult>(1);
 
   // Spawn the audio thread; keep all CPAL types inside this thread.
-  let path_clone = wav_path.clone();
-  thread::Builder::new()
+  let handle = thread::Builder::new()
     .name("recorder".into())
     .spawn(move || {
-      if let Err(e) = run_audio_thread(rx, &path_clone) {
-        eprintln!("audio thread failed: {e:?}");
-      }
+      let result = run_audio_thread(&job, rx, ready_tx);
+      ack_tx.send(result).ok();
     })?;
 
+  // Catch failures that happen while opening the stream (device busy,
+  // unsupported format, file create error) so the caller hears about them now
+  // and not only at stop. A slow device that misses the window is left
+  // running; anything that goes wrong later is reported by stop_recording.
+  // With a pre-roll the stream only opens after the countdown, so there's
+  // nothing to wait for yet.
+  let wait = if preroll > 0 { Duration::ZERO } else { START_TIMEOUT };
+  let actual = match ready_rx.recv_timeout(wait) {
+    Ok(actual) => Some(actual),
+    Err(RecvTimeoutError::Timeout) => None,
+    Err(RecvTimeoutError::Disconnected) => {
+      handle.join().ok();
+      let err = match ack_rx.try_recv() {
+        Ok(Err(e)) => e,
+        _ => RecorderError::other("audio thread exited during startup"),
+      };
+      tracing::error!("recording {session_id} failed to start: {err}");
+      // A resumed session's file holds the earlier recording; keep it.
+      if !appending {
+        fs::remove_file(&path).ok();
+      }
+      return Err(err.into());
+    }
+  };
+
+  tracing::info!("recording {session_id} started: {}", path.display());
   state.tx = Some(tx);
+  state.ack_rx = Some(ack_rx);
+  state.handle = Some(handle);
   state.session_id = Some(session_id.clone());
-  state.last_wav = Some(wav_path.clone());
+  state.last_path = Some(final_path);
   state.paused = false;
+  state.device_name = resolved_name;
+  state.config = actual.clone();
+  state.elapsed_ms = elapsed_ms;
 
-  Ok((session_id, wav_path))
+  Ok(StartedRecording {
+    session_id,
+    config: actual,
+    already_running: false,
+  })
+}
+
+pub fn recording_status(state: &mut RecorderState) -> RecorderStatus {
+  reap_finished(state);
+  let active = state.tx.is_some();
+  RecorderStatus {
+    active,
+    paused: active && state.paused,
+    session_id: if active { state.session_id.clone() } else { None },
+    elapsed_secs: if active {
+      state.elapsed_ms.load(Ordering::Relaxed) as f64 / 1000.0
+    } else {
+      0.0
+    },
+  }
 }
 
 pub fn pause_recording(state: &mut RecorderState) -> Result<()> {
+  reap_finished(state);
   if let Some(tx) = &state.tx {
     tx.send(Cmd::Pause).map_err(|e| anyhow!(e.to_string()))?;
     state.paused = true;
@@ -99,6 +1002,7 @@ pub fn pause_recording(state: &mut RecorderState) -> Result<()> {
 }
 
 pub fn resume_recording(state: &mut RecorderState) -> Result<()> {
+  reap_finished(state);
   if let Some(tx) = &state.tx {
     tx.send(Cmd::Resume).map_err(|e| anyhow!(e.to_string()))?;
     state.paused = false;
@@ -108,68 +1012,1294 @@ pub fn resume_recording(state: &mut RecorderState) -> Result<()> {
   }
 }
 
-pub fn stop_recording(state: &mut RecorderState) -> Result<PathBuf> {
-  if let Some(tx) = state.tx.take() {
-    // Ignore send error if thread already exited
-    tx.send(Cmd::Stop).ok();
-  } else {
-    return Err(anyhow!("no active recording"));
+/// Asks the audio thread to bookmark the current position. Returns roughly
+/// where that is; the exact time lands in the session metadata on stop.
+pub fn add_marker(state: &mut RecorderState, label: Option<String>) -> Result<f64> {
+  reap_finished(state);
+  let tx = state.tx.as_ref().ok_or_else(|| anyhow!("no active recording"))?;
+  tx.send(Cmd::Marker(label)).map_err(|e| anyhow!(e.to_string()))?;
+  Ok(state.elapsed_ms.load(Ordering::Relaxed) as f64 / 1000.0)
+}
+
+/// Changes the input gain of the running recording; takes effect on the next
+/// block. Returns the gain actually applied after clamping.
+pub fn set_input_gain(state: &mut RecorderState, db: f32) -> Result<f32> {
+  reap_finished(state);
+  let tx = state.tx.as_ref().ok_or_else(|| anyhow!("no active recording"))?;
+  let db = clamp_gain_db(db);
+  tx.send(Cmd::SetGain(db)).map_err(|e| anyhow!(e.to_string()))?;
+  if let Some(config) = &mut state.config {
+    config.input_gain_db = db;
   }
-  // We could join the thread or wait for an ACK; for now, return the last path.
-  let out = state
-    .last_wav
-    .clone()
-    .ok_or_else(|| anyhow!("no wav produced"))?;
-  Ok(out)
+  Ok(db)
+}
+
+pub fn stop_recording(state: &mut RecorderState) -> Result<StoppedRecording> {
+  reap_finished(state);
+  // An auto-stopped session is already finalized; hand that result back
+  // rather than erroring with "no active recording".
+  let result = match state.finished.take() {
+    Some(result) => result,
+    None => {
+      let tx = state
+        .tx
+        .take()
+        .ok_or_else(|| anyhow!("no active recording"))?;
+      let cmd = match short_recording(state) {
+        Some(ShortStop::Refuse(msg)) => {
+          state.tx = Some(tx);
+          return Err(anyhow!(msg));
+        }
+        Some(ShortStop::Discard) => Cmd::Cancel,
+        None => Cmd::Stop,
+      };
+      // Ignore send error if thread already exited
+      tx.send(cmd).ok();
+      wait_for_thread(state)?
+    }
+  };
+  // Whatever made it to disk before a failure is finalized, but the caller
+  // still needs to know the recording ended early.
+  let report = result?;
+  let path = match report.cancelled {
+    true => None,
+    false => Some(
+      state
+        .last_path
+        .clone()
+        .ok_or_else(|| anyhow!("no recording produced"))?,
+    ),
+  };
+  Ok(StoppedRecording {
+    path,
+    device_name: state.device_name.clone(),
+    duration_secs: report.duration_secs(),
+    clipping_pct: report.clipping_pct,
+  })
+}
+
+enum ShortStop {
+  Refuse(String),
+  Discard,
+}
+
+// What to do with a stop that comes before settings.min_duration_secs.
+// With a minimum set, a stop before anything was recorded (during the
+// countdown, or a double tap right after start) is discarded even when
+// short ones are refused; there's nothing to keep.
+fn short_recording(state: &RecorderState) -> Option<ShortStop> {
+  let settings = load_settings();
+  let min = settings.min_duration_secs.unwrap_or(0.0);
+  let elapsed = state.elapsed_ms.load(Ordering::Relaxed) as f64 / 1000.0;
+  if min <= 0.0 || elapsed >= min {
+    return None;
+  }
+  if elapsed == 0.0 {
+    return Some(ShortStop::Discard);
+  }
+  Some(match settings.discard_short {
+    true => ShortStop::Discard,
+    false => ShortStop::Refuse(format!(
+      "[too-short] only {elapsed:.1}s recorded; the minimum is {min:.1}s"
+    )),
+  })
+}
+
+/// Stops the recording and throws away what it captured: a new session's
+/// file is deleted without ever showing up in storage, and a resumed one is
+/// cut back to where it was. Leaves the recorder idle, like stop_recording.
+pub fn cancel_recording(state: &mut RecorderState) -> Result<()> {
+  reap_finished(state);
+  let tx = state.tx.take().ok_or_else(|| anyhow!("no active recording"))?;
+  tx.send(Cmd::Cancel).ok();
+  wait_for_thread(state)??;
+  Ok(())
+}
+
+// Waits for the thread's ACK and joins it. Only stop paths call this, after
+// the thread has been told to stop (or has stopped on its own).
+fn wait_for_thread(state: &mut RecorderState) -> Result<ThreadResult> {
+  // The thread ACKs after finalize; a dropped sender means it bailed out.
+  let ack_rx = state
+    .ack_rx
+    .take()
+    .ok_or_else(|| anyhow!("no active recording"))?;
+  let handle = state.handle.take();
+  let result = match ack_rx.recv_timeout(STOP_TIMEOUT) {
+    Ok(result) => result,
+    Err(RecvTimeoutError::Timeout) => {
+      // Leave the handle detached; joining would hang this command too.
+      return Err(anyhow!(
+        "audio thread did not finish within {}s; the input device may be stuck",
+        STOP_TIMEOUT.as_secs()
+      ));
+    }
+    Err(RecvTimeoutError::Disconnected) => {
+      if let Some(h) = handle {
+        h.join().ok();
+      }
+      return Err(anyhow!("audio thread exited without finishing the recording"));
+    }
+  };
+  // The ACK is the thread's last act, so this returns right away.
+  if let Some(h) = handle {
+    h.join().map_err(|_| anyhow!("audio thread panicked"))?;
+  }
+  Ok(result)
+}
+
+// If the thread ended on its own (auto-stop, device loss), clear the live
+// handles so the state reads as idle, and park its result for stop_recording.
+fn reap_finished(state: &mut RecorderState) {
+  let done = state.handle.as_ref().is_some_and(|h| h.is_finished());
+  if !done {
+    return;
+  }
+  state.tx = None;
+  state.paused = false;
+  state.finished = Some(match wait_for_thread(state) {
+    Ok(result) => result,
+    Err(e) => Err(RecorderError::other(e)),
+  });
+}
+
+/// What `test_microphone` heard, in 0.0..=1.0 of full scale over the whole
+/// test. `hint` says what to check when nothing came in.
+#[derive(Debug, Clone, Serialize)]
+pub struct MicTestReport {
+  pub device_name: Option<String>,
+  pub peak: f32,
+  pub rms: f32,
+  pub signal_detected: bool,
+  pub hint: Option<String>,
+}
+
+/// Holds the input for a microphone test: while it lives, starting or
+/// resuming a recording is refused. The recorder lock isn't held for the
+/// test itself, so this is what keeps the two from opening the device at
+/// once. From `begin_mic_test`; released on drop.
+#[derive(Debug)]
+pub struct MicTest(Arc<AtomicBool>);
+
+impl Drop for MicTest {
+  fn drop(&mut self) {
+    self.0.store(false, Ordering::Relaxed);
+  }
+}
+
+/// Claims the input for `test_microphone`. Refused while a recording or
+/// another test is running.
+pub fn begin_mic_test(state: &mut RecorderState) -> Result<MicTest> {
+  reap_finished(state);
+  if state.tx.is_some() {
+    return Err(anyhow!("recording in progress; stop it before testing the microphone"));
+  }
+  if state.mic_test.swap(true, Ordering::Relaxed) {
+    return Err(anyhow!("a microphone test is already running"));
+  }
+  Ok(MicTest(state.mic_test.clone()))
+}
+
+/// Captures from an input device for `seconds` (1 to 10), emitting
+/// `recording://level` as a recording would, and reports the levels seen.
+/// Nothing is written to disk. `_claim` is held until the capture ends.
+pub fn test_microphone(
+  _claim: MicTest,
+  app: &AppHandle,
+  device_name: Option<&str>,
+  seconds: f64,
+) -> Result<MicTestReport> {
+  let host = cpal::default_host();
+  let device = find_input_device(&host, device_name).map_err(RecorderError::device)?;
+  let supported = device.default_input_config().map_err(RecorderError::device)?;
+  let (err_tx, err_rx) = unbounded::<cpal::StreamError>();
+  let input = CpalSource::open(&device, supported, err_tx).map_err(RecorderError::device)?;
+  input.start().map_err(RecorderError::device)?;
+
+  let mut meter = LevelMeter::new(input.sample_rate(), input.channels());
+  let (mut peak, mut sum_sq, mut count) = (0.0_f32, 0.0_f64, 0_u64);
+  let deadline = Instant::now() + Duration::from_secs_f64(seconds.clamp(1.0, MAX_MIC_TEST_SECS));
+  loop {
+    if let Ok(e) = err_rx.try_recv() {
+      return Err(RecorderError::device(e).into());
+    }
+    let block = match input.blocks().recv_deadline(deadline) {
+      Ok(block) => block,
+      Err(RecvTimeoutError::Timeout) => break,
+      Err(RecvTimeoutError::Disconnected) => {
+        return Err(RecorderError::device("input stream closed").into())
+      }
+    };
+    for s in block {
+      peak = peak.max(s.abs());
+      sum_sq += (s as f64) * (s as f64);
+      count += 1;
+      if let Some(level) = meter.push(s) {
+        if meter.due() {
+          app.emit(LEVEL_EVENT, level).ok();
+        }
+      }
+    }
+  }
+
+  let signal_detected = peak >= SIGNAL_FLOOR;
+  let hint = (!signal_detected).then(|| {
+    "no signal detected; check that the mic is unmuted, its input level is up, and the app \
+     is allowed to use the microphone"
+      .to_string()
+  });
+  Ok(MicTestReport {
+    device_name: device.name().ok(),
+    peak: peak.min(1.0),
+    rms: (sum_sq / count.max(1) as f64).sqrt() as f32,
+    signal_detected,
+    hint,
+  })
 }
 
 // ---- audio thread ----
 
-// NOTE: This stub writes silence to WAV, but it’s structured so you can
-// drop in CPAL device/stream creation INSIDE this function without making
-// the outer `RecorderState` non-Send.
-fn run_audio_thread(rx: Receiver<Cmd>, wav_path: &Path) -> Result<()> {
-  // Example WAV writer
+// All CPAL types (host, device, stream) are created and dropped inside this
+// function so the outer `RecorderState` stays `Send`. Capture sits behind a
+// `SampleSource`, opened here by the job's `open_source`, that only converts
+// samples to f32 and pushes them over a channel; this loop owns the output
+// `Sink` and decides what actually gets written, waking whenever the source,
+// the control channel or the error channel has something.
+fn run_audio_thread(
+  job: &AudioJob,
+  rx: Receiver<Cmd>,
+  ready_tx: Sender<RecordingConfig>,
+) -> ThreadResult {
+  let (path, elapsed_ms) = (&job.path, &job.elapsed_ms);
+  let wanted = job.config.as_ref();
+  let preroll = wanted.map_or(0, |w| w.preroll_secs);
+  if preroll > 0 && !count_down(job, &rx, preroll) {
+    tracing::info!("recording {} cancelled during countdown", job.session_id);
+    return Ok(ThreadReport {
+      frames_written: 0,
+      sample_rate: 0,
+      clipping_pct: 0.0,
+      cancelled: true,
+    });
+  }
+  // A new session starts when the countdown ends, not when it was asked for.
+  let started_at = match (&job.existing, preroll) {
+    (None, 1..) => chrono::Local::now().to_rfc3339(),
+    _ => job.started_at.clone(),
+  };
+  let format = wanted.map_or(OutputFormat::Wav, |w| w.format);
+  let source = wanted.map_or(CaptureSource::Mic, |w| w.source);
+  let (stream_err_tx, stream_err_rx) = unbounded::<cpal::StreamError>();
+  let input = (job.open_source)(job, stream_err_tx.clone())?;
+
+  // Match the WAV to what the device actually delivers.
+  let downmix = wanted.is_some_and(|w| w.downmix_to_mono);
+  let bits = match format {
+    OutputFormat::Wav => wanted.map_or(16, |w| w.bits_per_sample),
+    OutputFormat::Mp3 | OutputFormat::Flac => 16,
+  };
+  let resample_to = wanted
+    .and_then(|w| w.resample_to)
+    .filter(|&rate| rate > 0 && rate != input.sample_rate());
   let spec = WavSpec {
-    channels: 1,
-    sample_rate: 16_000,
-    bits_per_sample: 16,
-    sample_format: SampleFormat::Int,
+    channels: if downmix { 1 } else { input.channels() },
+    sample_rate: resample_to.unwrap_or(input.sample_rate()),
+    bits_per_sample: if matches!(bits, 24 | 32) { bits } else { 16 },
+    sample_format: if bits == 32 { SampleFormat::Float } else { SampleFormat::Int },
+  };
+  // Appending needs the new audio in exactly the file's format; frames
+  // already in it count towards elapsed time, markers and the cap.
+  let base_frames = match &job.existing {
+    Some(_) => {
+      let reader = hound::WavReader::open(path).map_err(RecorderError::disk)?;
+      let on_disk = reader.spec();
+      if on_disk != spec {
+        return Err(RecorderError::device(format!(
+          "can't continue a {} Hz / {} ch / {}-bit recording: the device opened at {} Hz / {} ch",
+          on_disk.sample_rate,
+          on_disk.channels,
+          on_disk.bits_per_sample,
+          spec.sample_rate,
+          spec.channels
+        )));
+      }
+      reader.duration() as u64
+    }
+    None => 0,
   };
-  let mut writer = WavWriter::create(wav_path, spec)?;
 
+  let samples_rx = input.blocks();
+  let mut sink = match job.existing {
+    Some(_) => Sink::append(path)?,
+    None => Sink::create(path, spec, format)?,
+  };
+  input.start().map_err(RecorderError::device)?;
+  let mut loopback = match source {
+    CaptureSource::Both => Some(LoopbackMix::open(&cpal::default_host(), &spec, stream_err_tx)?),
+    CaptureSource::Mic | CaptureSource::System => None,
+  };
+  let gain_db = clamp_gain_db(wanted.map_or(0.0, |w| w.input_gain_db));
+  ready_tx
+    .send(RecordingConfig {
+      sample_rate: spec.sample_rate,
+      channels: spec.channels,
+      bits_per_sample: spec.bits_per_sample,
+      input_gain_db: gain_db,
+      ..wanted.cloned().unwrap_or_default()
+    })
+    .ok();
+
+  let max_frames = wanted
+    .and_then(|w| w.max_duration_secs)
+    .map(|secs| (secs.max(0.0) * spec.sample_rate as f64) as u64);
+  // Silence is judged per meter block, so short gaps between words reset it.
+  let silence = wanted.and_then(|w| w.silence_timeout_secs.map(|t| (t, w.silence_threshold)));
+  let mut quiet_secs = 0.0_f64;
+  let mut gain = db_to_linear(gain_db);
+  let mut gate = wanted.and_then(|w| {
+    let threshold = w.gate_threshold?;
+    Some(NoiseGate::new(threshold, w.gate_attenuation_db, spec.sample_rate, spec.channels))
+  });
+
+  let channels = spec.channels.max(1) as usize;
   let mut paused = false;
   let mut running = true;
+  let mut cancelled = false;
+  let mut frames_written: u64 = base_frames;
+  elapsed_ms.store(frames_written * 1000 / spec.sample_rate as u64, Ordering::Relaxed);
+  let mut failure: Option<RecorderError> = None;
+  let mut auto_stop: Option<AutoStopReason> = None;
+  // A resumed session picks up its earlier markers, and the gap counts as
+  // one more pause.
+  let mut paused_segments: u32 = job.existing.as_ref().map_or(0, |m| m.paused_segments + 1);
+  let mut markers: Vec<Marker> = job.existing.as_ref().map_or(Vec::new(), |m| m.markers.clone());
+  let mut meter = LevelMeter::new(spec.sample_rate, spec.channels);
+  // Counted as samples are written so stop doesn't need a second pass over
+  // the file. A resumed session's earlier share is estimated from its meta.
+  let mut samples_total: u64 = base_frames * channels as u64;
+  let mut samples_clipped: u64 = job
+    .existing
+    .as_ref()
+    .and_then(|m| m.clipping_pct)
+    .map_or(0, |pct| (pct as f64 / 100.0 * samples_total as f64) as u64);
+  // Samples of a frame that straddled two callback blocks. Only whole frames
+  // are written, so the channel interleaving can never drift.
+  let in_channels = input.channels().max(1) as usize;
+  let mut partial: Vec<f32> = Vec::with_capacity(in_channels);
+  let mut resampler = resample_to.map(|to| FrameResampler::new(input.sample_rate(), to));
+  let mut resampled: Vec<f32> = Vec::new();
+  let mut live = match (&job.app, wanted.is_some_and(|w| w.live_transcribe)) {
+    (Some(app), true) => {
+      let offset_secs = base_frames as f64 / spec.sample_rate as f64;
+      LiveTranscriber::start(app.clone(), job.session_id.clone(), spec.sample_rate, offset_secs)
+        .inspect_err(|e| tracing::warn!("live transcription unavailable: {e}"))
+        .ok()
+    }
+    _ => None,
+  };
+  let mut last_disk_check = Instant::now();
+  let mut last_device_check = Instant::now();
+  let mut last_tick: Option<Instant> = None;
 
   while running {
-    // Poll for control messages; in a real impl you'd also pull audio frames
-    // from CPAL callback into a ring buffer and write here when !paused.
-    if let Ok(cmd) = rx.try_recv() {
+    // A stream error (usually the device going away) ends the session; keep
+    // what we have and report where it stopped.
+    let at = frames_written as f64 / spec.sample_rate as f64;
+    let mut lost = false;
+    if let Ok(e) = stream_err_rx.try_recv() {
+      match e {
+        cpal::StreamError::DeviceNotAvailable => lost = true,
+        other => {
+          failure =
+            Some(RecorderError::device(format!("input stream failed at {at:.1}s: {other}")));
+          break;
+        }
+      }
+    }
+    if last_device_check.elapsed() >= DEVICE_CHECK_INTERVAL {
+      last_device_check = Instant::now();
+      lost |= !input.present();
+    }
+    if lost {
+      let event = DeviceLostEvent {
+        session_id: job.session_id.clone(),
+        device_name: job.device_label.clone(),
+        at_secs: at,
+      };
+      job.emit(DEVICE_LOST_EVENT, event);
+      failure = Some(RecorderError::device(format!("input device disconnected at {at:.1}s")));
+      break;
+    }
+    if last_disk_check.elapsed() >= DISK_CHECK_INTERVAL {
+      last_disk_check = Instant::now();
+      let dir = path.parent().unwrap_or(path);
+      if free_space(dir).is_ok_and(|free| free < MIN_FREE_WHILE_RECORDING) {
+        auto_stop = Some(AutoStopReason::LowDisk);
+        break;
+      }
+    }
+    if let Some(mix) = &mut loopback {
+      mix.pull(paused);
+    }
+    // Drain whatever the callback delivered since the last pass. The stream
+    // keeps running while paused, so those blocks are read and dropped here
+    // instead of piling up and being flushed into the file on resume.
+    'drain: while let Ok(block) = samples_rx.try_recv() {
+      if paused {
+        continue;
+      }
+      for &s in &block {
+        partial.push(s);
+        if partial.len() < in_channels {
+          continue;
+        }
+        if downmix && in_channels > 1 {
+          let mono = partial.iter().sum::<f32>() / in_channels as f32;
+          partial.clear();
+          partial.push(mono);
+        }
+        // Resampling turns one captured frame into none, one or several.
+        let frames = match &mut resampler {
+          Some(r) => {
+            resampled.clear();
+            r.push(&partial, &mut resampled);
+            &resampled[..]
+          }
+          None => &partial[..],
+        };
+        for frame in frames.chunks_exact(channels) {
+          let mut frame_sum = 0.0;
+          for &c in frame {
+            // Gain, then the gate, on the mic only; system audio is mixed in
+            // as-is.
+            let c = c * gain;
+            let c = match &mut gate {
+              Some(gate) => gate.process(c),
+              None => c,
+            };
+            let c = match &mut loopback {
+              Some(mix) => c + mix.next(),
+              None => c,
+            }
+            .clamp(-1.0, 1.0);
+            sink.write(c)?;
+            frame_sum += c;
+            samples_total += 1;
+            if c.abs() >= CLIP_LEVEL {
+              samples_clipped += 1;
+            }
+            if let Some(level) = meter.push(c) {
+              if let Some((timeout, threshold)) = silence {
+                if level.rms < threshold {
+                  quiet_secs += LEVEL_INTERVAL.as_secs_f64();
+                } else {
+                  quiet_secs = 0.0;
+                }
+                if quiet_secs >= timeout {
+                  auto_stop = Some(AutoStopReason::Silence);
+                }
+              }
+              if meter.due() {
+                job.emit(LEVEL_EVENT, level);
+              }
+            }
+          }
+          // The live transcript hears exactly what goes into the file.
+          if let Some(live) = &mut live {
+            live.push_frame(frame_sum / channels as f32);
+          }
+          frames_written += 1;
+          if max_frames.is_some_and(|max| frames_written >= max) {
+            auto_stop = Some(AutoStopReason::MaxDuration);
+          }
+          if auto_stop.is_some() {
+            break;
+          }
+        }
+        partial.clear();
+        if auto_stop.is_some() {
+          break 'drain;
+        }
+      }
+      elapsed_ms.store(frames_written * 1000 / spec.sample_rate as u64, Ordering::Relaxed);
+      if let Some(live) = &mut live {
+        live.flush();
+      }
+    }
+    if auto_stop.is_some() {
+      break;
+    }
+    // Commands are handled after the drain so audio captured before a pause
+    // still lands in the file.
+    let cmd = match rx.try_recv() {
+      Ok(cmd) => Some(cmd),
+      // The controlling state is gone; finish the file as if stopped.
+      Err(TryRecvError::Disconnected) => Some(Cmd::Stop),
+      Err(TryRecvError::Empty) => None,
+    };
+    if let Some(cmd) = cmd {
       match cmd {
-        Cmd::Pause => paused = true,
-        Cmd::Resume => paused = false,
+        Cmd::Pause => {
+          if !paused {
+            paused_segments += 1;
+          }
+          paused = true;
+          // Resume must start on a fresh frame boundary.
+          partial.clear();
+        }
+        Cmd::Resume => {
+          // Anything queued since the last drain was captured while paused.
+          samples_rx.try_iter().for_each(drop);
+          if let Some(mix) = &mut loopback {
+            mix.clear();
+          }
+          // Don't interpolate across the gap.
+          if let Some(r) = &mut resampler {
+            r.reset();
+          }
+          paused = false;
+        }
         Cmd::Stop => {
           running = false;
           continue;
         }
+        Cmd::Cancel => {
+          cancelled = true;
+          running = false;
+          continue;
+        }
+        Cmd::Marker(label) => {
+          let label = label.map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+          markers.push(Marker {
+            secs: frames_written as f64 / spec.sample_rate as f64,
+            label,
+          });
+        }
+        Cmd::SetGain(db) => gain = db_to_linear(db),
       }
     }
-    if !paused {
-      let sample: i16 = 0; // silence placeholder
-      writer.write_sample(sample)?;
+    if last_tick.is_none_or(|t| t.elapsed() >= TICK_INTERVAL) {
+      last_tick = Some(Instant::now());
+      let tick = TickEvent {
+        elapsed_secs: frames_written as f64 / spec.sample_rate as f64,
+        paused,
+      };
+      job.emit(TICK_EVENT, tick);
+    }
+    // Block until audio, a command or a stream error arrives instead of
+    // polling on a timer. `ready` consumes nothing, so the next pass still
+    // drains audio before looking at commands.
+    let mut sel = Select::new();
+    sel.recv(samples_rx);
+    sel.recv(&rx);
+    sel.recv(&stream_err_rx);
+    sel.ready_timeout(IDLE_WAKE).ok();
+  }
+
+  // Stop capturing before finalizing so no callback races the header write.
+  drop(input);
+  drop(loopback);
+  // Lets the live transcript finish the tail in the background.
+  if let Some(live) = &mut live {
+    live.flush();
+  }
+  drop(live);
+  sink.finalize()?;
+  if cancelled {
+    discard_recorded(job, &spec, frames_written - base_frames).map_err(RecorderError::disk)?;
+    tracing::info!("recording {} cancelled", job.session_id);
+    return Ok(ThreadReport {
+      frames_written: base_frames,
+      sample_rate: spec.sample_rate,
+      clipping_pct: 0.0,
+      cancelled: true,
+    });
+  }
+  // Only a complete file leaves `.tmp`. It moves even when the device
+  // failed, since what was captured up to then is intact.
+  let path = &job.final_path;
+  if job.path != *path {
+    fs::rename(&job.path, path).map_err(RecorderError::disk)?;
+  }
+  let report = ThreadReport {
+    frames_written,
+    sample_rate: spec.sample_rate,
+    clipping_pct: if samples_total == 0 {
+      0.0
+    } else {
+      (samples_clipped as f64 * 100.0 / samples_total as f64) as f32
+    },
+    cancelled: false,
+  };
+  // Written even when the device failed, since the partial file is kept.
+  let meta = SessionMeta {
+    started_at,
+    duration_secs: report.duration_secs(),
+    device_name: job.device_label.clone(),
+    paused_segments,
+    markers,
+    clipping_pct: Some(report.clipping_pct),
+    tags: job.existing.as_ref().map_or(Vec::new(), |m| m.tags.clone()),
+    uid: job.uid.clone(),
+    source_hash: job.existing.as_ref().and_then(|m| m.source_hash.clone()),
+    ..SessionMeta::new(&job.session_id, spec.sample_rate, spec.channels)
+  };
+  if let Some(dir) = path.parent() {
+    if let Err(e) = write_meta(dir, &meta) {
+      tracing::warn!("writing session metadata failed: {e}");
+    }
+  }
+  tracing::info!(
+    "recording {} finished: {:.1}s, {paused_segments} pause(s), {:.2}% clipped",
+    job.session_id,
+    report.duration_secs(),
+    report.clipping_pct
+  );
+  if report.clipping_pct > CLIPPING_WARN_PCT {
+    let event = ClippingWarningEvent {
+      session_id: job.session_id.clone(),
+      clipping_pct: report.clipping_pct,
+    };
+    job.emit(CLIPPING_EVENT, event);
+  }
+  // Nobody called stop, so no command will report these.
+  if failure.is_some() || auto_stop.is_some() {
+    let event = StateEvent {
+      state: "stopped",
+      session_id: Some(job.session_id.clone()),
+      elapsed_secs: report.duration_secs(),
+    };
+    job.emit(STATE_EVENT, event);
+  }
+  if let Some(e) = failure {
+    tracing::warn!("recording {} ended early: {e}", job.session_id);
+    return Err(e);
+  }
+  if let Some(reason) = auto_stop {
+    tracing::info!("recording {} auto-stopped: {reason:?}", job.session_id);
+    let event = AutoStopEvent {
+      reason,
+      path: path.to_string_lossy().to_string(),
+      duration_secs: report.duration_secs(),
+    };
+    job.emit(AUTO_STOPPED_EVENT, event);
+  }
+  Ok(report)
+}
+
+// The real capture for a job: its mic by name, or the output device in
+// loopback, opened in the config closest to what was asked for.
+fn open_device(
+  job: &AudioJob,
+  err_tx: Sender<cpal::StreamError>,
+) -> RecorderResult<Box<dyn SampleSource>> {
+  let wanted = job.config.as_ref();
+  let source = wanted.map_or(CaptureSource::Mic, |w| w.source);
+  let host = cpal::default_host();
+  let device = match source {
+    CaptureSource::System => find_loopback_device(&host),
+    CaptureSource::Mic | CaptureSource::Both => {
+      find_input_device(&host, job.device_name.as_deref())
     }
-    // Tiny sleep to keep the loop from burning CPU in this stub.
-    std::thread::sleep(std::time::Duration::from_millis(10));
   }
-  writer.finalize()?;
+  .map_err(RecorderError::device)?;
+  let supported = match (source, wanted) {
+    // Loopback runs in shared mode and can only deliver the mix format.
+    (CaptureSource::System, _) => device.default_output_config().map_err(anyhow::Error::from),
+    (_, Some(w)) => choose_stream_config(&device, w),
+    (_, None) => device.default_input_config().map_err(anyhow::Error::from),
+  }
+  .map_err(RecorderError::device)?;
+  let input = CpalSource::open(&device, supported, err_tx).map_err(RecorderError::device)?;
+  // Loopback follows the output device; only a mic can be looked up by name.
+  Ok(Box::new(match source {
+    CaptureSource::System => input,
+    CaptureSource::Mic | CaptureSource::Both => input.watching(host, job.device_name.clone()),
+  }))
+}
+
+// Undoes a cancelled run. A new session's file still sits in `.tmp` and
+// just goes; a resumed WAV has the `added` frames cut off its end, and its
+// metadata was never touched.
+fn discard_recorded(job: &AudioJob, spec: &WavSpec, added: u64) -> Result<()> {
+  if job.existing.is_none() {
+    fs::remove_file(&job.path)?;
+    return Ok(());
+  }
+  let frame_bytes = spec.channels as u64 * (spec.bits_per_sample as u64 / 8);
+  let file = fs::OpenOptions::new().write(true).open(&job.path)?;
+  let len = file.metadata()?.len();
+  file.set_len(len.saturating_sub(added * frame_bytes))?;
+  drop(file);
+  repair_wav_header(&job.path)?;
   Ok(())
 }
 
-fn new_session_id() -> String {
-  // simple timestamp-based id; feel free to switch to uuid if preferred
+// Emits the pre-roll ticks a second apart. Returns false if the recording
+// was stopped before it began; other commands are dropped, since nothing is
+// being recorded yet.
+fn count_down(job: &AudioJob, rx: &Receiver<Cmd>, secs: u32) -> bool {
+  for remaining_secs in (1..=secs).rev() {
+    let event = CountdownEvent { session_id: job.session_id.clone(), remaining_secs };
+    job.emit(COUNTDOWN_EVENT, event);
+    let deadline = Instant::now() + Duration::from_secs(1);
+    loop {
+      match rx.recv_deadline(deadline) {
+        Ok(Cmd::Stop | Cmd::Cancel) | Err(RecvTimeoutError::Disconnected) => return false,
+        Ok(_) => {}
+        Err(RecvTimeoutError::Timeout) => break,
+      }
+    }
+  }
+  true
+}
+
+// How much converted system audio may queue up ahead of the mic. The two
+// devices run on separate clocks; past this the oldest samples are dropped
+// so the mix doesn't drift further and further out of sync.
+const LOOPBACK_MAX_QUEUE: Duration = Duration::from_millis(500);
+
+// The loopback half of `CaptureSource::Both`. System audio arrives in the
+// output device's format, gets downmixed and resampled to the mic's, and is
+// added sample by sample as mic frames are written.
+struct LoopbackMix {
+  source: CpalSource,
+  to_rate: u32,
+  to_channels: u16,
+  queue: VecDeque<f32>,
+}
+
+impl LoopbackMix {
+  fn open(
+    host: &cpal::Host,
+    target: &WavSpec,
+    err_tx: Sender<cpal::StreamError>,
+  ) -> RecorderResult<Self> {
+    let device = find_loopback_device(host).map_err(RecorderError::device)?;
+    let supported = device.default_output_config().map_err(RecorderError::device)?;
+    let source = CpalSource::open(&device, supported, err_tx).map_err(RecorderError::device)?;
+    source.start().map_err(RecorderError::device)?;
+    Ok(Self {
+      source,
+      to_rate: target.sample_rate,
+      to_channels: target.channels.max(1),
+      queue: VecDeque::new(),
+    })
+  }
+
+  // Converts whatever arrived since the last call; while paused it's dropped.
+  fn pull(&mut self, paused: bool) {
+    while let Ok(block) = self.source.blocks().try_recv() {
+      if paused {
+        continue;
+      }
+      let mono = to_mono(&block, self.source.channels());
+      for s in resample_linear(&mono, self.source.sample_rate(), self.to_rate) {
+        self.queue.extend(std::iter::repeat_n(s, self.to_channels as usize));
+      }
+    }
+    let max = (self.to_rate as f64 * LOOPBACK_MAX_QUEUE.as_secs_f64()) as usize
+      * self.to_channels as usize;
+    if self.queue.len() > max {
+      self.queue.drain(..self.queue.len() - max);
+    }
+  }
+
+  // The system sample to add to the next mic sample; silence if none queued.
+  fn next(&mut self) -> f32 {
+    self.queue.pop_front().unwrap_or(0.0)
+  }
+
+  fn clear(&mut self) {
+    self.source.blocks().try_iter().for_each(drop);
+    self.queue.clear();
+  }
+}
+
+type Writer = WavWriter<BufWriter<fs::File>>;
+
+// Where captured samples go: a WAV written here, or 16-bit PCM piped into an
+// ffmpeg process that encodes to `path` (MP3/FLAC) as it goes.
+enum Sink {
+  Wav(Writer, WavSpec),
+  Ffmpeg { child: Child, stdin: BufWriter<ChildStdin> },
+}
+
+impl Sink {
+  fn create(path: &Path, spec: WavSpec, format: OutputFormat) -> RecorderResult<Self> {
+    let Some(codec) = format.ffmpeg_codec() else {
+      let writer = WavWriter::create(path, spec).map_err(RecorderError::disk)?;
+      return Ok(Sink::Wav(writer, spec));
+    };
+    let ffmpeg = require_tool("ffmpeg", "install ffmpeg to record as MP3 or FLAC")
+      .map_err(RecorderError::other)?;
+    let mut child = Command::new(ffmpeg)
+      .args(["-hide_banner", "-loglevel", "error", "-y", "-f", "s16le"])
+      .args(["-ar", &spec.sample_rate.to_string(), "-ac", &spec.channels.to_string()])
+      .args(["-i", "pipe:0"])
+      .args(codec)
+      .arg(path)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::null())
+      .stderr(Stdio::piped())
+      .spawn()
+      .map_err(RecorderError::other)?;
+    let stdin = child
+      .stdin
+      .take()
+      .ok_or_else(|| RecorderError::other("ffmpeg stdin unavailable"))?;
+    Ok(Sink::Ffmpeg { child, stdin: BufWriter::new(stdin) })
+  }
+
+  // Reopens a finished WAV to add to the end; the header is rewritten on
+  // finalize.
+  fn append(path: &Path) -> RecorderResult<Self> {
+    let writer = WavWriter::append(path).map_err(RecorderError::disk)?;
+    let spec = writer.spec();
+    Ok(Sink::Wav(writer, spec))
+  }
+
+  fn write(&mut self, s: f32) -> RecorderResult<()> {
+    match self {
+      Sink::Wav(writer, spec) => write_sample(writer, spec, s).map_err(RecorderError::disk),
+      // A write failing here means ffmpeg has exited; finalize says why.
+      Sink::Ffmpeg { stdin, .. } => stdin
+        .write_all(&i16::from_sample(s).to_le_bytes())
+        .map_err(|e| RecorderError::disk(format!("encoder stopped: {e}"))),
+    }
+  }
+
+  fn finalize(self) -> RecorderResult<()> {
+    match self {
+      Sink::Wav(writer, _) => writer.finalize().map_err(RecorderError::disk),
+      Sink::Ffmpeg { child, mut stdin } => {
+        stdin.flush().ok();
+        // Closing stdin is ffmpeg's cue to finish the file and exit.
+        drop(stdin);
+        let out = child.wait_with_output().map_err(RecorderError::disk)?;
+        if !out.status.success() {
+          let stderr = String::from_utf8_lossy(&out.stderr);
+          return Err(RecorderError::disk(format!("ffmpeg failed: {}", stderr.trim())));
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+// Picks the supported input config closest to what was asked for: matching
+// channel count first, then the nearest sample rate within that range.
+fn choose_stream_config(
+  device: &cpal::Device,
+  wanted: &RecordingConfig,
+) -> Result<cpal::SupportedStreamConfig> {
+  let usable = |f: cpal::SampleFormat| {
+    matches!(f, cpal::SampleFormat::F32 | cpal::SampleFormat::I16 | cpal::SampleFormat::U16)
+  };
+  device
+    .supported_input_configs()?
+    .filter(|r| usable(r.sample_format()))
+    .map(|r| {
+      let rate = wanted
+        .sample_rate
+        .clamp(r.min_sample_rate().0, r.max_sample_rate().0);
+      let score = (r.channels().abs_diff(wanted.channels), rate.abs_diff(wanted.sample_rate));
+      (score, r.with_sample_rate(cpal::SampleRate(rate)))
+    })
+    .min_by_key(|(score, _)| *score)
+    .map(|(_, cfg)| cfg)
+    .ok_or_else(|| anyhow!("input device offers no usable capture config"))
+}
+
+// Where the audio thread's samples come from: interleaved f32 blocks in a
+// fixed rate and channel count, handed over on a channel. The loop only
+// ever reads that channel, so it doesn't care what fills it.
+trait SampleSource {
+  fn sample_rate(&self) -> u32;
+  fn channels(&self) -> u16;
+  fn blocks(&self) -> &Receiver<Vec<f32>>;
+  // Begin delivering blocks.
+  fn start(&self) -> Result<()>;
+  // Whether the device is still attached; polled every few seconds. A
+  // source that can't tell says it is.
+  fn present(&self) -> bool {
+    true
+  }
+}
+
+// A live CPAL capture stream (a mic, or an output device in loopback).
+// Stream errors go to the `err_tx` it was opened with, so mic and loopback
+// failures land in one place. Dropping it stops the callback.
+struct CpalSource {
+  stream: cpal::Stream,
+  config: cpal::StreamConfig,
+  blocks: Receiver<Vec<f32>>,
+  // Host and device name to look the device up by; see `watching`.
+  watched: Option<(cpal::Host, Option<String>)>,
+}
+
+impl CpalSource {
+  fn open(
+    device: &cpal::Device,
+    supported: cpal::SupportedStreamConfig,
+    err_tx: Sender<cpal::StreamError>,
+  ) -> Result<Self> {
+    let sample_format = supported.sample_format();
+    let config: cpal::StreamConfig = supported.into();
+    let (tx, blocks) = unbounded::<Vec<f32>>();
+    let stream = open_stream(device, &config, sample_format, tx, err_tx)?;
+    Ok(Self { stream, config, blocks, watched: None })
+  }
+
+  // Has `present` look the device up again by name, so an unplugged mic is
+  // noticed even when the stream itself reports nothing.
+  fn watching(self, host: cpal::Host, device_name: Option<String>) -> Self {
+    Self { watched: Some((host, device_name)), ..self }
+  }
+}
+
+impl SampleSource for CpalSource {
+  fn sample_rate(&self) -> u32 {
+    self.config.sample_rate.0
+  }
+
+  fn channels(&self) -> u16 {
+    self.config.channels
+  }
+
+  fn blocks(&self) -> &Receiver<Vec<f32>> {
+    &self.blocks
+  }
+
+  fn start(&self) -> Result<()> {
+    self.stream.play()?;
+    Ok(())
+  }
+
+  fn present(&self) -> bool {
+    match &self.watched {
+      Some((host, name)) => input_device_present(host, name.as_deref()),
+      None => true,
+    }
+  }
+}
+
+// Opens a capture stream in whatever sample format the device uses,
+// converting to f32 in the callback.
+fn open_stream(
+  device: &cpal::Device,
+  config: &cpal::StreamConfig,
+  sample_format: cpal::SampleFormat,
+  tx: Sender<Vec<f32>>,
+  err_tx: Sender<cpal::StreamError>,
+) -> Result<cpal::Stream> {
+  match sample_format {
+    cpal::SampleFormat::F32 => build_input_stream::<f32>(device, config, tx, err_tx),
+    cpal::SampleFormat::I16 => build_input_stream::<i16>(device, config, tx, err_tx),
+    cpal::SampleFormat::U16 => build_input_stream::<u16>(device, config, tx, err_tx),
+    other => Err(anyhow!("unsupported input sample format: {other:?}")),
+  }
+}
+
+fn build_input_stream<T>(
+  device: &cpal::Device,
+  config: &cpal::StreamConfig,
+  tx: Sender<Vec<f32>>,
+  err_tx: Sender<cpal::StreamError>,
+) -> Result<cpal::Stream>
+where
+  T: SizedSample,
+  f32: FromSample<T>,
+{
+  let stream = device.build_input_stream(
+    config,
+    move |data: &[T], _: &cpal::InputCallbackInfo| {
+      let block: Vec<f32> = data.iter().map(|&s| f32::from_sample(s)).collect();
+      // Receiver is gone once the writer loop exits; nothing to do then.
+      tx.send(block).ok();
+    },
+    move |e| {
+      err_tx.send(e).ok();
+    },
+    None,
+  )?;
+  Ok(stream)
+}
+
+// A label as a file stem: sanitized, with runs of whitespace as underscores.
+fn label_stem(label: &str) -> Result<String> {
+  Ok(sanitize_name(label)?.split_whitespace().collect::<Vec<_>>().join("_"))
+}
+
+/// `sess-<millis>-<uuid>`. The timestamp keeps ids roughly in order in a
+/// file manager; the random v4 part means two sessions created in the same
+/// millisecond (a recording and an import, say) can't collide. Hex and
+/// dashes only, so it's safe as a file stem. Listing order comes from the
+/// metadata's `started_at`, not from the id.
+pub fn new_session_id() -> String {
   let ts = SystemTime::now()
     .duration_since(SystemTime::UNIX_EPOCH)
-    .unwrap()
+    .unwrap_or_default()
     .as_millis();
-  format!("sess-{ts}")
+  format!("sess-{ts}-{}", uuid::Uuid::new_v4().simple())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tools::{find_tool, run_tool};
+  use std::ffi::OsStr;
+
+  // Delivers whatever the test pushes, in a fixed rate and channel count.
+  struct ScriptedSource {
+    sample_rate: u32,
+    channels: u16,
+    blocks: Receiver<Vec<f32>>,
+  }
+
+  impl SampleSource for ScriptedSource {
+    fn sample_rate(&self) -> u32 {
+      self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+      self.channels
+    }
+
+    fn blocks(&self) -> &Receiver<Vec<f32>> {
+      &self.blocks
+    }
+
+    fn start(&self) -> Result<()> {
+      Ok(())
+    }
+  }
+
+  fn scripted(sample_rate: u32, channels: u16, blocks: Receiver<Vec<f32>>) -> OpenSource {
+    Box::new(move |_: &AudioJob, _: Sender<cpal::StreamError>| {
+      let source: Box<dyn SampleSource> =
+        Box::new(ScriptedSource { sample_rate, channels, blocks: blocks.clone() });
+      Ok(source)
+    })
+  }
+
+  // A recording running on its own audio thread, fed from the test. Each
+  // step waits for the loop to take what it was given, so the order blocks
+  // and commands are handled in is the order they were sent.
+  struct Run {
+    dir: PathBuf,
+    path: PathBuf,
+    cmds: Sender<Cmd>,
+    blocks: Sender<Vec<f32>>,
+    thread: JoinHandle<ThreadResult>,
+  }
+
+  impl Run {
+    fn start(name: &str, rate: u32, channels: u16, config: Option<RecordingConfig>) -> Self {
+      let dir = std::env::temp_dir().join(format!("applesauce-{name}-{}", std::process::id()));
+      fs::remove_dir_all(&dir).ok();
+      fs::create_dir_all(&dir).unwrap();
+      let ext = config.as_ref().map_or(OutputFormat::Wav, |c| c.format).extension();
+      let path = dir.join(format!("{name}.{ext}"));
+      let (blocks, blocks_rx) = unbounded::<Vec<f32>>();
+      let job = AudioJob {
+        session_id: name.to_string(),
+        path: path.clone(),
+        final_path: path.clone(),
+        device_name: None,
+        device_label: None,
+        config,
+        started_at: chrono::Local::now().to_rfc3339(),
+        existing: None,
+        uid: None,
+        reserved: None,
+        open_source: scripted(rate, channels, blocks_rx),
+        app: None,
+        elapsed_ms: Arc::new(AtomicU64::new(0)),
+      };
+      let (cmds, rx) = unbounded::<Cmd>();
+      let (ready_tx, ready_rx) = bounded::<RecordingConfig>(1);
+      let thread = thread::spawn(move || run_audio_thread(&job, rx, ready_tx));
+      ready_rx.recv_timeout(Duration::from_secs(5)).expect("source never started");
+      Self { dir, path, cmds, blocks, thread }
+    }
+
+    fn feed(&self, block: Vec<f32>) {
+      self.blocks.send(block).unwrap();
+      wait_until(|| self.blocks.is_empty());
+    }
+
+    fn send(&self, cmd: Cmd) {
+      self.cmds.send(cmd).unwrap();
+      wait_until(|| self.cmds.is_empty());
+    }
+
+    // Stops the recording and reads back what it wrote. MP3 and FLAC are
+    // decoded to a WAV first.
+    fn stop(self) -> (ThreadReport, WavSpec, Vec<i16>) {
+      self.cmds.send(Cmd::Stop).unwrap();
+      let report = self.thread.join().unwrap().expect("recording failed");
+      let wav = match self.path.extension().is_some_and(|e| e == "wav") {
+        true => self.path.clone(),
+        false => {
+          let wav = self.path.with_extension("decoded.wav");
+          let ffmpeg = find_tool("ffmpeg").expect("ffmpeg is needed to decode");
+          let args = [OsStr::new("-v"), OsStr::new("error"), OsStr::new("-i")];
+          run_tool(&ffmpeg, args.iter().copied().chain([self.path.as_os_str(), wav.as_os_str()]))
+            .unwrap();
+          wav
+        }
+      };
+      let mut reader = hound::WavReader::open(&wav).unwrap();
+      let samples = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+      let spec = reader.spec();
+      fs::remove_dir_all(&self.dir).ok();
+      (report, spec, samples)
+    }
+  }
+
+  fn wait_until(done: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+      assert!(Instant::now() < deadline, "audio thread stalled");
+      thread::sleep(Duration::from_millis(1));
+    }
+  }
+
+  // `len` consecutive 16-bit values from `from`, as the f32 a device hands
+  // over for them. Each one survives the trip into a 16-bit WAV exactly.
+  fn ramp(from: i16, len: i16) -> Vec<f32> {
+    (from..from + len).map(f32::from_sample).collect()
+  }
+
+  fn ints(samples: &[f32]) -> Vec<i16> {
+    samples.iter().map(|&s| i16::from_sample(s)).collect()
+  }
+
+  #[test]
+  fn pause_and_resume_write_exactly_the_unpaused_audio() {
+    let run = Run::start("ramp", 16_000, 1, None);
+    run.feed(ramp(0, 100));
+    run.send(Cmd::Pause);
+    run.feed(ramp(100, 50));
+    run.send(Cmd::Resume);
+    run.feed(ramp(150, 100));
+    let (report, spec, samples) = run.stop();
+
+    let expected = [ints(&ramp(0, 100)), ints(&ramp(150, 100))].concat();
+    assert_eq!(samples, expected);
+    assert_eq!(report.frames_written, 200);
+    assert_eq!((spec.sample_rate, spec.channels, spec.bits_per_sample), (16_000, 1, 16));
+  }
+
+  #[test]
+  fn duration_counts_only_unpaused_audio() {
+    // 100 ms blocks at 16 kHz: 1.5 s, a second while paused, then 1 s more.
+    let run = Run::start("duration", 16_000, 1, None);
+    let block = || vec![0.1; 1_600];
+    (0..15).for_each(|_| run.feed(block()));
+    run.send(Cmd::Pause);
+    (0..10).for_each(|_| run.feed(block()));
+    run.send(Cmd::Resume);
+    (0..10).for_each(|_| run.feed(block()));
+    let (report, spec, samples) = run.stop();
+
+    assert!((report.duration_secs() - 2.5).abs() < 0.01, "{}", report.duration_secs());
+    assert_eq!(samples.len() as u32, spec.sample_rate * 5 / 2);
+  }
+
+  #[test]
+  fn stereo_frames_stay_interleaved_across_blocks_and_pauses() {
+    let (left, right) = (f32::from_sample(1000_i16), f32::from_sample(-1000_i16));
+    let frames = |n: usize| [left, right].repeat(n);
+    let run = Run::start("stereo", 16_000, 2, None);
+    // A frame split over two blocks, then a pause that strands half of one.
+    run.feed(vec![left, right, left]);
+    run.feed(vec![right, left, right]);
+    run.feed(vec![left]);
+    run.send(Cmd::Pause);
+    run.feed(frames(4));
+    run.send(Cmd::Resume);
+    run.feed(frames(3));
+    let (report, spec, samples) = run.stop();
+
+    assert_eq!(spec.channels, 2);
+    assert_eq!(report.frames_written, 6);
+    assert_eq!(samples, [1000, -1000].repeat(6));
+  }
+
+  #[test]
+  fn audio_delivered_while_paused_never_reaches_the_file() {
+    let run = Run::start("paused", 16_000, 1, None);
+    run.feed(ramp(0, 10));
+    run.send(Cmd::Pause);
+    // Queued right before the resume, as a live stream's callback would;
+    // the loop may or may not have drained them by the time it resumes.
+    for from in [1000, 2000, 3000] {
+      run.blocks.send(ramp(from, 10)).unwrap();
+    }
+    run.send(Cmd::Resume);
+    run.feed(ramp(10, 10));
+    let (_, _, samples) = run.stop();
+
+    assert_eq!(samples, (0..20).collect::<Vec<i16>>());
+  }
+
+  #[test]
+  #[ignore = "needs ffmpeg"]
+  fn flac_recording_decodes_back_to_the_captured_samples() {
+    find_tool("ffmpeg").expect("ffmpeg not installed");
+    let config = RecordingConfig { format: OutputFormat::Flac, ..Default::default() };
+    let run = Run::start("flac", 16_000, 1, Some(config));
+    let captured: Vec<f32> = (0..4_000).flat_map(|_| ramp(-4, 8)).collect();
+    for block in captured.chunks(1_600) {
+      run.feed(block.to_vec());
+    }
+    let (report, spec, samples) = run.stop();
+
+    assert_eq!(spec.sample_rate, 16_000);
+    assert_eq!(samples.len(), captured.len());
+    assert_eq!(report.frames_written, captured.len() as u64);
+    assert_eq!(samples, ints(&captured));
+  }
+
+  #[test]
+  fn session_ids_made_back_to_back_never_collide() {
+    // Many share a millisecond; the random part has to tell them apart.
+    let ids: Vec<String> = (0..10_000).map(|_| new_session_id()).collect();
+    let unique: std::collections::HashSet<&String> = ids.iter().collect();
+    assert_eq!(unique.len(), ids.len());
+    assert!(ids.iter().all(|id| validate_session_id(id).is_ok()));
+  }
+
+  // `secs` of a 440 Hz sine at 16 kHz.
+  fn sine(amplitude: f32, secs: usize) -> Vec<f32> {
+    (0..16_000 * secs)
+      .map(|i| amplitude * (i as f32 * 440.0 * std::f32::consts::TAU / 16_000.0).sin())
+      .collect()
+  }
+
+  #[test]
+  fn gate_passes_speech_and_turns_hiss_down_by_its_attenuation() {
+    let mut gate = NoiseGate::new(0.01, -40.0, 16_000, 1);
+    let speech = sine(0.5, 1);
+    let out: Vec<f32> = speech.iter().map(|&s| gate.process(s)).collect();
+    assert_eq!(out, speech);
+
+    // Past the hold and the release glide, hiss comes out 40 dB down.
+    let hiss = sine(0.002, 2);
+    let out: Vec<f32> = hiss.iter().map(|&s| gate.process(s)).collect();
+    let tail = 24_000..;
+    let [hiss_peak, out_peak] = [&hiss[tail.clone()], &out[tail]]
+      .map(|x| x.iter().fold(0.0_f32, |m, s| m.max(s.abs())));
+    let attenuation_db = 20.0 * (out_peak / hiss_peak).log10();
+    assert!((attenuation_db + 40.0).abs() < 0.5, "{attenuation_db} dB");
+
+    // Speech opens it again within a few milliseconds.
+    let out: Vec<f32> = speech.iter().map(|&s| gate.process(s)).collect();
+    let settled = 800;
+    for (o, s) in out[settled..].iter().zip(&speech[settled..]) {
+      assert!((o - s).abs() < 1e-3, "{o} vs {s}");
+    }
+  }
 }
@@ -0,0 +1,145 @@
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+  collections::HashMap,
+  fs,
+  path::{Path, PathBuf},
+  time::SystemTime,
+};
+
+use crate::transcribe::Transcript;
+
+const TRANSCRIPT_SUFFIX: &str = ".transcript.json";
+// Plenty for a results list; a one-letter query shouldn't return thousands.
+const MAX_MATCHES: usize = 200;
+// Snippet length when a transcript has no segments to quote from.
+const SNIPPET_CHARS: usize = 160;
+
+/// One hit from `search_transcripts`. `start_secs`/`end_secs` come from the
+/// matching segment, so the UI can seek playback there; they're None when
+/// the transcript has no segment timings.
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptMatch {
+  pub session_id: String,
+  pub snippet: String,
+  pub start_secs: Option<f64>,
+  pub end_secs: Option<f64>,
+}
+
+// A transcript as loaded for searching, with lowercased text alongside.
+struct Indexed {
+  modified: SystemTime,
+  segments: Vec<(f64, f64, String, String)>,
+  text: String,
+  text_lower: String,
+}
+
+// Transcripts loaded by earlier searches, keyed by file. Each search only
+// re-reads files whose mtime changed, so repeat searches don't parse every
+// transcript again.
+static INDEX: Mutex<Option<HashMap<PathBuf, (String, Indexed)>>> = Mutex::new(None);
+
+/// Finds `query` (case-insensitive) in every saved transcript under `dir`,
+/// one match per segment that contains it, sorted by session and time.
+pub fn search_transcripts(dir: &Path, query: &str) -> Result<Vec<TranscriptMatch>> {
+  let query = query.trim().to_lowercase();
+  if query.is_empty() {
+    return Ok(Vec::new());
+  }
+  let mut guard = INDEX.lock();
+  let index = guard.get_or_insert_with(HashMap::new);
+  refresh(index, dir)?;
+
+  let mut out = Vec::new();
+  for (session_id, t) in index.values() {
+    if !t.text_lower.contains(&query) {
+      continue;
+    }
+    if t.segments.is_empty() {
+      out.push(TranscriptMatch {
+        session_id: session_id.clone(),
+        snippet: snippet(&t.text, &t.text_lower, &query),
+        start_secs: None,
+        end_secs: None,
+      });
+      continue;
+    }
+    for (start, end, text, lower) in &t.segments {
+      if lower.contains(&query) {
+        out.push(TranscriptMatch {
+          session_id: session_id.clone(),
+          snippet: text.clone(),
+          start_secs: Some(*start),
+          end_secs: Some(*end),
+        });
+      }
+    }
+  }
+  out.sort_by(|a, b| {
+    a.session_id
+      .cmp(&b.session_id)
+      .then(a.start_secs.unwrap_or(0.0).total_cmp(&b.start_secs.unwrap_or(0.0)))
+  });
+  out.truncate(MAX_MATCHES);
+  Ok(out)
+}
+
+// Brings the index in line with the transcripts currently in `dir`.
+fn refresh(index: &mut HashMap<PathBuf, (String, Indexed)>, dir: &Path) -> Result<()> {
+  if !dir.is_dir() {
+    index.clear();
+    return Ok(());
+  }
+  let mut seen = Vec::new();
+  for entry in fs::read_dir(dir)?.flatten() {
+    let path = entry.path();
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else { continue };
+    let Some(session_id) = name.strip_suffix(TRANSCRIPT_SUFFIX) else { continue };
+    let Ok(modified) = entry.metadata().and_then(|m| m.modified()) else { continue };
+    seen.push(path.clone());
+    if index.get(&path).is_some_and(|(_, t)| t.modified == modified) {
+      continue;
+    }
+    // An unreadable transcript just doesn't show up in results.
+    let Some(transcript) = fs::read(&path)
+      .ok()
+      .and_then(|b| serde_json::from_slice::<Transcript>(&b).ok())
+    else {
+      index.remove(&path);
+      continue;
+    };
+    index.insert(path, (session_id.to_string(), indexed(transcript, modified)));
+  }
+  index.retain(|path, _| seen.contains(path));
+  Ok(())
+}
+
+fn indexed(transcript: Transcript, modified: SystemTime) -> Indexed {
+  let segments = transcript
+    .segments
+    .into_iter()
+    .map(|s| {
+      let lower = s.text.to_lowercase();
+      (s.start, s.end, s.text, lower)
+    })
+    .collect();
+  let text_lower = transcript.text.to_lowercase();
+  Indexed { modified, segments, text: transcript.text, text_lower }
+}
+
+// About SNIPPET_CHARS of `text` around the first match. Positions are
+// counted in chars of the lowercased text, which lines up with the original
+// closely enough for a preview.
+fn snippet(text: &str, lower: &str, query: &str) -> String {
+  let at = lower.find(query).map_or(0, |i| lower[..i].chars().count());
+  let start = at.saturating_sub(SNIPPET_CHARS / 2);
+  let mut out: String = text.chars().skip(start).take(SNIPPET_CHARS).collect();
+  if start > 0 {
+    out.insert(0, '…');
+  }
+  if text.chars().count() > start + SNIPPET_CHARS {
+    out.push('…');
+  }
+  out
+}
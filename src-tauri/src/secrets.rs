@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Result};
+use keyring::Entry;
+use std::{fs, path::Path};
+
+use crate::storage::APP_IDENTIFIER;
+
+const API_KEY_USER: &str = "api_key";
+// Real OpenAI keys are far longer; this only catches truncated pastes.
+const MIN_API_KEY_LEN: usize = 20;
+
+fn api_key_entry() -> Result<Entry> {
+  Entry::new(APP_IDENTIFIER, API_KEY_USER).map_err(|e| anyhow!("OS keychain unavailable: {e}"))
+}
+
+/// Cheap offline check of an OpenAI key's shape; returns it trimmed.
+pub fn validate_api_key(value: &str) -> Result<String> {
+  let key = value.trim();
+  if key.is_empty() {
+    return Err(anyhow!("API key is empty"));
+  }
+  if !key.starts_with("sk-") {
+    return Err(anyhow!("OpenAI API keys start with 'sk-'; check that the whole key was pasted"));
+  }
+  if key.len() < MIN_API_KEY_LEN || key.contains(char::is_whitespace) {
+    return Err(anyhow!("API key looks incomplete; copy it again from the OpenAI dashboard"));
+  }
+  Ok(key.to_string())
+}
+
+/// Stores the API key in the OS credential store (Keychain, Credential
+/// Manager, Secret Service) rather than on disk. Malformed keys are refused.
+pub fn save_api_key(value: &str) -> Result<()> {
+  let key = validate_api_key(value)?;
+  api_key_entry()?
+    .set_password(&key)
+    .map_err(|e| anyhow!("could not save API key to the keychain: {e}"))
+}
+
+pub fn read_api_key() -> Result<Option<String>> {
+  match api_key_entry()?.get_password() {
+    Ok(key) => Ok(Some(key)),
+    Err(keyring::Error::NoEntry) => Ok(None),
+    Err(e) => Err(anyhow!("could not read API key from the keychain: {e}")),
+  }
+}
+
+/// Moves a key left in the old plaintext `apikey.txt` into the keychain and
+/// deletes the file. Nothing to do if the file is gone.
+pub fn migrate_plaintext_key(legacy: &Path) -> Result<()> {
+  if !legacy.exists() {
+    return Ok(());
+  }
+  let key = fs::read_to_string(legacy)?;
+  let key = key.trim();
+  // A malformed legacy key is dropped rather than kept on disk.
+  if validate_api_key(key).is_ok() && read_api_key()?.is_none() {
+    save_api_key(key)?;
+  }
+  fs::remove_file(legacy)?;
+  Ok(())
+}
@@ -0,0 +1,422 @@
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::BTreeSet,
+  fs,
+  path::{Path, PathBuf},
+  time::UNIX_EPOCH,
+};
+
+use crate::transcribe::read_transcript;
+use crate::trash::trash_session;
+
+/// Extensions a session's recording can have: what the recorder writes (see
+/// `OutputFormat`), plus the browser formats save_audio_base64 accepts.
+pub const RECORDING_EXTENSIONS: [&str; 9] =
+  ["wav", "mp3", "flac", "webm", "ogg", "oga", "opus", "m4a", "aac"];
+
+pub fn is_recording(path: &Path) -> bool {
+  path
+    .extension()
+    .and_then(|e| e.to_str())
+    .is_some_and(|e| RECORDING_EXTENSIONS.iter().any(|r| e.eq_ignore_ascii_case(r)))
+}
+
+/// The session's audio file, whichever format it was recorded in.
+pub fn find_recording(dir: &Path, session_id: &str) -> Option<PathBuf> {
+  RECORDING_EXTENSIONS
+    .iter()
+    .map(|ext| dir.join(format!("{session_id}.{ext}")))
+    .find(|p| p.is_file())
+}
+
+/// Sidecar `<session_id>.json` written next to each recording, so the UI can
+/// list sessions without parsing WAV headers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMeta {
+  pub session_id: String,
+  // RFC 3339, local time with offset.
+  pub started_at: String,
+  pub duration_secs: f64,
+  pub sample_rate: u32,
+  pub channels: u16,
+  pub device_name: Option<String>,
+  pub paused_segments: u32,
+  // Bookmarks dropped with add_marker_cmd, in recording order.
+  #[serde(default)]
+  pub markers: Vec<Marker>,
+  // Percent of samples at or near full scale. None for audio we didn't
+  // record ourselves (imports, merges).
+  #[serde(default)]
+  pub clipping_pct: Option<f32>,
+  // Set with set_session_tags_cmd; trimmed, no duplicates.
+  #[serde(default)]
+  pub tags: Vec<String>,
+  // Generated id (`sess-...`) given at recording time. `session_id` is the
+  // file stem, which a label or rename changes; this stays put. None for
+  // imports, edits and sessions recorded before it existed.
+  #[serde(default)]
+  pub uid: Option<String>,
+  // SHA-1 of the file it was imported from, for spotting re-imports.
+  #[serde(default)]
+  pub source_hash: Option<String>,
+}
+
+impl SessionMeta {
+  /// A session starting now, with nothing else known about it yet. Callers
+  /// fill in the rest with struct update syntax.
+  pub fn new(session_id: &str, sample_rate: u32, channels: u16) -> Self {
+    Self {
+      session_id: session_id.to_string(),
+      started_at: chrono::Local::now().to_rfc3339(),
+      duration_secs: 0.0,
+      sample_rate,
+      channels,
+      device_name: None,
+      paused_segments: 0,
+      markers: Vec::new(),
+      clipping_pct: None,
+      tags: Vec::new(),
+      uid: None,
+      source_hash: None,
+    }
+  }
+}
+
+/// A point of interest in a recording. `secs` is into the recorded audio,
+/// so paused time doesn't count.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Marker {
+  pub secs: f64,
+  pub label: Option<String>,
+}
+
+/// One row of `list_sessions_cmd`. Built from the sidecar JSON when present,
+/// otherwise from the WAV header and filesystem.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+  pub session_id: String,
+  // The recording, despite the name: `.mp3`/`.flac` sessions land here too.
+  pub wav_path: String,
+  pub started_at: Option<String>,
+  pub duration_secs: f64,
+  pub size_bytes: u64,
+  pub sample_rate: u32,
+  pub channels: u16,
+  pub device_name: Option<String>,
+  pub markers: Vec<Marker>,
+  pub tags: Vec<String>,
+  pub has_meta: bool,
+  // Millis since epoch, for newest-first ordering.
+  #[serde(skip)]
+  sort_key: i64,
+}
+
+impl SessionSummary {
+  /// Start time in millis since epoch (file mtime when there's no sidecar).
+  pub fn started_ms(&self) -> i64 {
+    self.sort_key
+  }
+}
+
+pub fn meta_path(dir: &Path, session_id: &str) -> PathBuf {
+  dir.join(format!("{session_id}.json"))
+}
+
+pub fn write_meta(dir: &Path, meta: &SessionMeta) -> Result<()> {
+  write_json_atomic(&meta_path(dir, &meta.session_id), meta)
+}
+
+/// Writes to a sibling temp file and renames it into place, so a crash
+/// mid-write leaves the previous file (or nothing) rather than half a JSON.
+pub fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+  let tmp = path.with_extension("json.tmp");
+  fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+  fs::rename(&tmp, path)?;
+  Ok(())
+}
+
+/// Reads a JSON file, falling back to the default when it's missing or
+/// unreadable.
+pub fn read_json_or_default<T: serde::de::DeserializeOwned + Default>(path: &Path) -> T {
+  fs::read(path)
+    .ok()
+    .and_then(|b| serde_json::from_slice(&b).ok())
+    .unwrap_or_default()
+}
+
+pub fn read_meta(dir: &Path, session_id: &str) -> Option<SessionMeta> {
+  let bytes = fs::read(meta_path(dir, session_id)).ok()?;
+  serde_json::from_slice(&bytes).ok()
+}
+
+/// Scans `dir` for `<id>.wav` / `.mp3` / `.flac` recordings, newest first. Files that aren't
+/// readable recordings are skipped rather than failing the whole listing.
+pub fn list_sessions(dir: &Path) -> Result<Vec<SessionSummary>> {
+  if !dir.exists() {
+    return Ok(Vec::new());
+  }
+  let mut out = Vec::new();
+  for entry in fs::read_dir(dir)?.flatten() {
+    let path = entry.path();
+    if !is_recording(&path) || !path.is_file() {
+      continue;
+    }
+    let Some(id) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+    // A browser save can have both its `.webm` and a `.wav` transcode; the
+    // session is listed once, by the file find_recording picks.
+    if find_recording(dir, id).as_deref() != Some(path.as_path()) {
+      continue;
+    }
+    if let Some(summary) = summarize(dir, id, &path) {
+      out.push(summary);
+    }
+  }
+  // Ties broken by id so pages don't shuffle between calls.
+  out.sort_by(|a, b| b.sort_key.cmp(&a.sort_key).then_with(|| a.session_id.cmp(&b.session_id)));
+  Ok(out)
+}
+
+/// A slice of `list_sessions`, with the total so the UI can page.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionPage {
+  pub sessions: Vec<SessionSummary>,
+  pub offset: usize,
+  pub total: usize,
+}
+
+/// `limit` sessions (all of them when None) starting `offset` in from the
+/// newest. An offset past the end gives an empty page, not an error.
+pub fn list_sessions_page(dir: &Path, offset: usize, limit: Option<usize>) -> Result<SessionPage> {
+  let all = list_sessions(dir)?;
+  let total = all.len();
+  let sessions = all.into_iter().skip(offset).take(limit.unwrap_or(usize::MAX)).collect();
+  Ok(SessionPage { sessions, offset, total })
+}
+
+/// The listing row for one session.
+pub fn session_summary(dir: &Path, session_id: &str) -> Result<SessionSummary> {
+  validate_session_id(session_id)?;
+  find_recording(dir, session_id)
+    .and_then(|audio| summarize(dir, session_id, &audio))
+    .ok_or_else(|| anyhow!("session '{session_id}' not found"))
+}
+
+fn summarize(dir: &Path, id: &str, wav: &Path) -> Option<SessionSummary> {
+  let fs_meta = fs::metadata(wav).ok()?;
+  let mtime_ms = fs_meta
+    .modified()
+    .ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map_or(0, |d| d.as_millis() as i64);
+  let wav_path = wav.to_string_lossy().to_string();
+
+  if let Some(meta) = read_meta(dir, id) {
+    let sort_key = chrono::DateTime::parse_from_rfc3339(&meta.started_at)
+      .map(|t| t.timestamp_millis())
+      .unwrap_or(mtime_ms);
+    return Some(SessionSummary {
+      session_id: meta.session_id,
+      wav_path,
+      started_at: Some(meta.started_at),
+      duration_secs: meta.duration_secs,
+      size_bytes: fs_meta.len(),
+      sample_rate: meta.sample_rate,
+      channels: meta.channels,
+      device_name: meta.device_name,
+      markers: meta.markers,
+      tags: meta.tags,
+      has_meta: true,
+      sort_key,
+    });
+  }
+
+  // No sidecar: fall back to the header. Unreadable WAVs aren't sessions,
+  // and neither are MP3/FLACs without one (the recorder always writes it).
+  let reader = hound::WavReader::open(wav).ok()?;
+  let spec = reader.spec();
+  Some(SessionSummary {
+    session_id: id.to_string(),
+    wav_path,
+    started_at: None,
+    duration_secs: reader.duration() as f64 / spec.sample_rate.max(1) as f64,
+    size_bytes: fs_meta.len(),
+    sample_rate: spec.sample_rate,
+    channels: spec.channels,
+    device_name: None,
+    markers: Vec::new(),
+    tags: Vec::new(),
+    has_meta: false,
+    sort_key: mtime_ms,
+  })
+}
+
+/// Rejects ids that could point outside the storage directory.
+pub fn validate_session_id(session_id: &str) -> Result<()> {
+  let bad = session_id.is_empty()
+    || session_id.contains(['/', '\\'])
+    || session_id.contains("..")
+    || Path::new(session_id).is_absolute();
+  if bad {
+    return Err(anyhow!("invalid session id: {session_id:?}"));
+  }
+  Ok(())
+}
+
+/// Replaces a session's tags. Blank tags are dropped and duplicates (ignoring
+/// case) collapsed; returns the tags as saved.
+pub fn set_session_tags(dir: &Path, session_id: &str, tags: &[String]) -> Result<Vec<String>> {
+  validate_session_id(session_id)?;
+  let mut meta = read_meta(dir, session_id)
+    .ok_or_else(|| anyhow!("session '{session_id}' not found or has no metadata"))?;
+  let mut cleaned: Vec<String> = Vec::new();
+  for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+    if !cleaned.iter().any(|c| c.eq_ignore_ascii_case(tag)) {
+      cleaned.push(tag.to_string());
+    }
+  }
+  meta.tags = cleaned.clone();
+  write_meta(dir, &meta)?;
+  Ok(cleaned)
+}
+
+/// Sessions whose name, a tag or the saved transcript contains `query`
+/// (case-insensitive), newest first. A blank query matches everything.
+pub fn search_sessions(dir: &Path, query: &str) -> Result<Vec<SessionSummary>> {
+  let query = query.trim().to_lowercase();
+  let mut out = list_sessions(dir)?;
+  if query.is_empty() {
+    return Ok(out);
+  }
+  out.retain(|s| {
+    s.session_id.to_lowercase().contains(&query)
+      || s.tags.iter().any(|t| t.to_lowercase().contains(&query))
+      || read_transcript(dir, &s.session_id)
+        .is_ok_and(|t| t.text.to_lowercase().contains(&query))
+  });
+  Ok(out)
+}
+
+/// Every file belonging to a session: `<id>.wav`, `<id>.json` and derived
+/// files like `<id>.transcript.json` or `<id>.peaks.json`.
+pub fn session_files(dir: &Path, session_id: &str) -> Result<Vec<PathBuf>> {
+  validate_session_id(session_id)?;
+  let prefix = format!("{session_id}.");
+  let mut out = Vec::new();
+  for entry in fs::read_dir(dir)?.flatten() {
+    let name = entry.file_name();
+    let matches = name.to_str().is_some_and(|n| n.starts_with(&prefix));
+    if matches && entry.path().is_file() {
+      out.push(entry.path());
+    }
+  }
+  Ok(out)
+}
+
+/// Turns a user-supplied label into a safe file stem: drops characters that
+/// are illegal in Windows filenames. Dots become underscores, since session
+/// files are grouped by the `<id>.` prefix and ids must not contain one.
+pub fn sanitize_name(name: &str) -> Result<String> {
+  const ILLEGAL: [char; 9] = ['\\', '/', ':', '*', '?', '"', '<', '>', '|'];
+  let cleaned: String = name
+    .chars()
+    .filter(|c| !ILLEGAL.contains(c) && !c.is_control())
+    .map(|c| if c == '.' { '_' } else { c })
+    .collect();
+  let cleaned = cleaned.trim().to_string();
+  if cleaned.is_empty() {
+    return Err(anyhow!("name is empty after removing invalid characters"));
+  }
+  Ok(cleaned)
+}
+
+// `<dir>/<stem>` paths handed out by reserve_stem whose files may not be
+// written yet: a recording still in `.tmp`, an import mid-copy.
+static RESERVED: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// A session id held by whoever is creating that session. Released on drop,
+/// by which point its files exist (or never will).
+#[derive(Debug)]
+pub struct ReservedStem {
+  pub id: String,
+  path: PathBuf,
+}
+
+impl Drop for ReservedStem {
+  fn drop(&mut self) {
+    RESERVED.lock().remove(&self.path);
+  }
+}
+
+/// Whether `<dir>/<id>` is held by a `ReservedStem`: a session still being
+/// created, whose files may be half-written.
+pub fn is_reserved(dir: &Path, id: &str) -> bool {
+  RESERVED.lock().contains(&dir.join(id))
+}
+
+/// First of `base`, `base_2`, `base_3`, ... with no files in `dir` and not
+/// reserved by another caller that hasn't written its files yet; reserves
+/// the one it returns. Everything that creates a session (recording,
+/// imports, edits) gets its id here, so two running at once can't end up
+/// with the same one.
+pub fn reserve_stem(dir: &Path, base: &str) -> Result<ReservedStem> {
+  let mut reserved = RESERVED.lock();
+  let mut candidate = base.to_string();
+  let mut n = 2;
+  while reserved.contains(&dir.join(&candidate)) || !session_files(dir, &candidate)?.is_empty() {
+    candidate = format!("{base}_{n}");
+    n += 1;
+  }
+  let path = dir.join(&candidate);
+  reserved.insert(path.clone());
+  Ok(ReservedStem { id: candidate, path })
+}
+
+/// Renames every `<id>.*` file to `<new>.*` and updates the id inside the
+/// metadata. Returns the id actually used (a suffix is added on collision).
+pub fn rename_session(dir: &Path, session_id: &str, new_name: &str) -> Result<String> {
+  let files = session_files(dir, session_id)?;
+  if files.is_empty() {
+    return Err(anyhow!("session '{session_id}' not found"));
+  }
+  let wanted = sanitize_name(new_name)?;
+  if wanted == session_id {
+    return Ok(wanted);
+  }
+  let reserved = reserve_stem(dir, &wanted)?;
+  let new_id = reserved.id.clone();
+  for f in files {
+    let name = f.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    // Keep everything after the id, e.g. ".wav" or ".transcript.json".
+    let rest = &name[session_id.len()..];
+    fs::rename(&f, dir.join(format!("{new_id}{rest}")))?;
+  }
+  if let Some(mut meta) = read_meta(dir, &new_id) {
+    meta.session_id = new_id.clone();
+    write_meta(dir, &meta)?;
+  }
+  Ok(new_id)
+}
+
+/// Moves sessions that started more than `max_age_days` ago to the trash
+/// and returns their ids. With `dry_run` nothing is moved; the ids are what
+/// would be. At least one day: 0 would put the cutoff at now and take
+/// every session.
+pub fn cleanup_old_sessions(dir: &Path, max_age_days: u32, dry_run: bool) -> Result<Vec<String>> {
+  if max_age_days == 0 {
+    return Err(anyhow!("max age must be at least 1 day"));
+  }
+  let cutoff = chrono::Local::now().timestamp_millis() - i64::from(max_age_days) * 86_400_000;
+  let mut removed = Vec::new();
+  for session in list_sessions(dir)? {
+    if session.started_ms() >= cutoff {
+      continue;
+    }
+    if !dry_run {
+      trash_session(dir, &session.session_id)?;
+    }
+    removed.push(session.session_id);
+  }
+  Ok(removed)
+}
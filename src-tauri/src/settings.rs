@@ -0,0 +1,111 @@
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fs, path::Path, path::PathBuf};
+
+use crate::recorder::RecordingConfig;
+use crate::sessions::write_json_atomic;
+use crate::storage::app_data_dir;
+
+// Serialises read-modify-write cycles so concurrent updates don't drop
+// each other's fields.
+static SETTINGS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Everything user-configurable, persisted as `settings.json` in the app
+/// data dir. The API key is not here; it lives in the OS keychain.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+  pub prompt_preset: Option<String>,
+  // Where recordings go; None = Downloads/ApplesauceCacheNative.
+  pub storage_dir: Option<String>,
+  // Trash sessions older than this many days at startup (at least 1);
+  // None = keep all.
+  pub retention_days: Option<u32>,
+  // Largest decoded blob save_audio_base64 accepts, in MB; None = 200.
+  pub max_save_mb: Option<u64>,
+  // Stopping with less recorded than this is refused; None/0 = any length.
+  pub min_duration_secs: Option<f64>,
+  // With min_duration_secs: throw a too-short recording away on stop
+  // instead of refusing the stop.
+  pub discard_short: bool,
+  // Retries of an OpenAI transcription after a 429/5xx or a dropped
+  // connection; None = 3. The wait doubles from openai_retry_base_ms
+  // (None = 1000) each time, unless the API says how long to wait.
+  pub openai_max_retries: Option<u32>,
+  pub openai_retry_base_ms: Option<u64>,
+  // What the last start_recording asked for, used when the next one leaves
+  // them out. Written by the recorder, not the settings screen.
+  pub last_device_name: Option<String>,
+  pub last_recording_config: Option<RecordingConfig>,
+}
+
+pub fn settings_path() -> PathBuf {
+  app_data_dir().join("settings.json")
+}
+
+/// Current settings; defaults if the file is missing or unreadable.
+pub fn load_settings() -> Settings {
+  let Ok(bytes) = fs::read(settings_path()) else { return Settings::default() };
+  serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+    tracing::warn!("settings.json is invalid, using defaults: {e}");
+    Settings::default()
+  })
+}
+
+fn save_settings(settings: &Settings) -> Result<()> {
+  fs::create_dir_all(app_data_dir())?;
+  write_json_atomic(&settings_path(), settings)
+}
+
+/// Merges a partial JSON object into the saved settings: keys present in
+/// `patch` replace the stored value (`null` clears it), others are kept.
+pub fn update_settings(patch: Value) -> Result<Settings> {
+  let Value::Object(patch) = patch else {
+    return Err(anyhow!("settings update must be a JSON object"));
+  };
+  let _guard = SETTINGS_LOCK.lock();
+  let Value::Object(mut merged) = serde_json::to_value(load_settings())? else {
+    unreachable!("Settings serializes to an object")
+  };
+  merged.extend(patch);
+  let settings: Settings = serde_json::from_value(Value::Object(merged))
+    .map_err(|e| anyhow!("invalid settings value: {e}"))?;
+  // Retention runs unattended at every launch; 0 days would take everything.
+  if settings.retention_days == Some(0) {
+    return Err(anyhow!("retention_days must be at least 1"));
+  }
+  save_settings(&settings)?;
+  Ok(settings)
+}
+
+/// Saves the device and config a recording was started with, so the next
+/// start can reuse them.
+pub fn remember_recording(
+  device_name: Option<String>,
+  config: Option<RecordingConfig>,
+) -> Result<()> {
+  let _guard = SETTINGS_LOCK.lock();
+  let mut settings = load_settings();
+  settings.last_device_name = device_name;
+  settings.last_recording_config = config;
+  save_settings(&settings)
+}
+
+/// One-time import of the old per-file `prompt.txt` from `legacy_dir`.
+pub fn migrate_legacy_files(legacy_dir: &Path) -> Result<()> {
+  let prompt_file = legacy_dir.join("prompt.txt");
+  if !prompt_file.exists() {
+    return Ok(());
+  }
+  let prompt = fs::read_to_string(&prompt_file)?;
+  let _guard = SETTINGS_LOCK.lock();
+  let mut settings = load_settings();
+  if settings.prompt_preset.is_none() {
+    settings.prompt_preset = Some(prompt);
+    save_settings(&settings)?;
+  }
+  fs::remove_file(prompt_file)?;
+  Ok(())
+}
@@ -0,0 +1,320 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{
+  collections::BTreeMap,
+  fs,
+  path::{Path, PathBuf},
+  process::{Command, Stdio},
+};
+
+use crate::sessions::RECORDING_EXTENSIONS;
+
+// Matches `identifier` in tauri.conf.json, so this is the same folder
+// Tauri's `app_data_dir()` resolves to, without needing an AppHandle.
+pub const APP_IDENTIFIER: &str = "com.yourname.applesauce";
+
+/// Private per-user app data (models, settings), separate from recordings.
+pub fn app_data_dir() -> PathBuf {
+  dirs::data_dir()
+    .unwrap_or_else(std::env::temp_dir)
+    .join(APP_IDENTIFIER)
+}
+
+/// Checks a user-chosen recordings folder: it must already exist, be a
+/// directory, and accept a test write. Returns it canonicalised.
+pub fn validate_storage_dir(path: &Path) -> Result<PathBuf> {
+  if !path.exists() {
+    return Err(anyhow!("folder does not exist: {}", path.display()));
+  }
+  if !path.is_dir() {
+    return Err(anyhow!("not a folder: {}", path.display()));
+  }
+  let probe = path.join(".applesauce-write-test");
+  fs::write(&probe, b"ok")
+    .and_then(|_| fs::remove_file(&probe))
+    .map_err(|e| anyhow!("can't write to {}: {e}", path.display()))?;
+  Ok(path.canonicalize()?)
+}
+
+/// Bytes available to us on the volume holding `dir`.
+pub fn free_space(dir: &Path) -> Result<u64> {
+  fs4::available_space(dir).map_err(|e| anyhow!("can't check free space on {}: {e}", dir.display()))
+}
+
+/// Opens the OS file manager with `path` selected: Explorer's `/select,` on
+/// Windows, `open -R` on macOS, and the freedesktop FileManager1 D-Bus call
+/// on Linux. Falls back to just opening the containing folder when that
+/// isn't available.
+pub fn reveal_path(path: &Path) -> Result<()> {
+  let selected = if cfg!(target_os = "macos") {
+    Command::new("open").arg("-R").arg(path).status().is_ok_and(|s| s.success())
+  } else if cfg!(windows) {
+    // Explorer exits non-zero even when it worked, so only a failed spawn
+    // counts.
+    explorer_select(path).is_ok()
+  } else {
+    let uri = file_uri(path);
+    Command::new("dbus-send")
+      .args(["--session", "--print-reply", "--dest=org.freedesktop.FileManager1"])
+      .args(["--type=method_call", "/org/freedesktop/FileManager1"])
+      .arg("org.freedesktop.FileManager1.ShowItems")
+      .arg(format!("array:string:{uri}"))
+      .arg("string:")
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .status()
+      .is_ok_and(|s| s.success())
+  };
+  if selected {
+    return Ok(());
+  }
+  let parent = path.parent().ok_or_else(|| anyhow!("{} has no parent folder", path.display()))?;
+  open::that(parent).map_err(|e| anyhow!("can't open {}: {e}", parent.display()))
+}
+
+#[cfg(windows)]
+fn explorer_select(path: &Path) -> std::io::Result<std::process::Child> {
+  use std::os::windows::process::CommandExt;
+  // Explorer wants `/select,"C:\a b\c.wav"` as one unmangled argument.
+  Command::new("explorer")
+    .raw_arg(format!("/select,\"{}\"", path.display()))
+    .spawn()
+}
+
+#[cfg(not(windows))]
+fn explorer_select(_path: &Path) -> std::io::Result<std::process::Child> {
+  Err(std::io::ErrorKind::Unsupported.into())
+}
+
+// `file://` URI with everything but unreserved characters and `/`
+// percent-encoded.
+fn file_uri(path: &Path) -> String {
+  let mut uri = String::from("file://");
+  for b in path.to_string_lossy().bytes() {
+    if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+      uri.push(b as char);
+    } else {
+      uri.push_str(&format!("%{b:02X}"));
+    }
+  }
+  uri
+}
+
+/// Result of clearing the storage directory: how many top-level entries went
+/// away and which ones couldn't be removed (with the reason).
+#[derive(Debug, Clone, Serialize)]
+pub struct ClearSummary {
+  pub removed: usize,
+  pub failures: Vec<String>,
+}
+
+/// Removes everything inside `dir` (files and subdirectories), but never
+/// `dir` itself. Per-entry failures are collected, not fatal.
+pub fn clear_dir_contents(dir: &Path) -> ClearSummary {
+  let mut summary = ClearSummary {
+    removed: 0,
+    failures: Vec::new(),
+  };
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return summary,
+    Err(e) => {
+      summary.failures.push(format!("{}: {e}", dir.display()));
+      return summary;
+    }
+  };
+  for entry in entries.flatten() {
+    let path = entry.path();
+    let result = if path.is_dir() {
+      fs::remove_dir_all(&path)
+    } else {
+      fs::remove_file(&path)
+    };
+    match result {
+      Ok(()) => summary.removed += 1,
+      Err(e) => summary.failures.push(format!("{}: {e}", path.display())),
+    }
+  }
+  summary
+}
+
+/// Disk use of one session: all of its `<id>.*` files.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionUsage {
+  pub session_id: String,
+  pub bytes: u64,
+  pub files: usize,
+}
+
+/// Result of `storage_usage_cmd`. `other_bytes` covers files that don't
+/// belong to a recording (PDF text, subfolders, ...).
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageUsage {
+  pub total_bytes: u64,
+  pub session_count: usize,
+  pub sessions: Vec<SessionUsage>,
+  pub other_bytes: u64,
+  // Entries that couldn't be read; their sizes are missing from the totals.
+  pub errors: Vec<String>,
+}
+
+// Sums file sizes under `dir`, recording unreadable entries instead of
+// stopping.
+fn dir_size(dir: &Path, errors: &mut Vec<String>) -> u64 {
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(e) => {
+      errors.push(format!("{}: {e}", dir.display()));
+      return 0;
+    }
+  };
+  let mut total = 0;
+  for entry in entries.flatten() {
+    let path = entry.path();
+    match entry.metadata() {
+      Ok(m) if m.is_dir() => total += dir_size(&path, errors),
+      Ok(m) => total += m.len(),
+      Err(e) => errors.push(format!("{}: {e}", path.display())),
+    }
+  }
+  total
+}
+
+/// Walks `dir` and totals its size, split per session (largest first).
+pub fn storage_usage(dir: &Path) -> StorageUsage {
+  let mut usage = StorageUsage {
+    total_bytes: 0,
+    session_count: 0,
+    sessions: Vec::new(),
+    other_bytes: 0,
+    errors: Vec::new(),
+  };
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return usage,
+    Err(e) => {
+      usage.errors.push(format!("{}: {e}", dir.display()));
+      return usage;
+    }
+  };
+
+  // Session files share the `<id>.` prefix; a group is a session if it
+  // has a recording (`<id>.wav`, `.mp3` or `.flac`).
+  let mut groups: BTreeMap<String, (u64, usize, bool)> = BTreeMap::new();
+  for entry in entries.flatten() {
+    let path = entry.path();
+    let meta = match entry.metadata() {
+      Ok(m) => m,
+      Err(e) => {
+        usage.errors.push(format!("{}: {e}", path.display()));
+        continue;
+      }
+    };
+    if meta.is_dir() {
+      usage.other_bytes += dir_size(&path, &mut usage.errors);
+      continue;
+    }
+    let name = entry.file_name().to_string_lossy().to_string();
+    let Some((id, rest)) = name.split_once('.') else {
+      usage.other_bytes += meta.len();
+      continue;
+    };
+    let group = groups.entry(id.to_string()).or_default();
+    group.0 += meta.len();
+    group.1 += 1;
+    group.2 |= RECORDING_EXTENSIONS.iter().any(|r| rest.eq_ignore_ascii_case(r));
+  }
+
+  for (session_id, (bytes, files, is_session)) in groups {
+    if is_session {
+      usage.sessions.push(SessionUsage { session_id, bytes, files });
+    } else {
+      usage.other_bytes += bytes;
+    }
+  }
+  usage.sessions.sort_by_key(|s| std::cmp::Reverse(s.bytes));
+  usage.session_count = usage.sessions.len();
+  usage.total_bytes = usage.other_bytes + usage.sessions.iter().map(|s| s.bytes).sum::<u64>();
+  usage
+}
+
+/// Outcome of moving recordings to a new folder. Files listed in
+/// `failures` are still in the old folder, untouched.
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveReport {
+  pub moved: usize,
+  pub failures: Vec<String>,
+}
+
+// Copy-then-delete fallback for when `rename` can't cross filesystems.
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+  if from.is_dir() {
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+      let entry = entry?;
+      copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+  } else {
+    fs::copy(from, to).map(|_| ())
+  }
+}
+
+fn move_entry(from: &Path, to: &Path) -> std::io::Result<()> {
+  if fs::rename(from, to).is_ok() {
+    return Ok(());
+  }
+  let copied = copy_recursive(from, to).and_then(|_| {
+    if from.is_dir() {
+      fs::remove_dir_all(from)
+    } else {
+      fs::remove_file(from)
+    }
+  });
+  if copied.is_err() {
+    // Don't leave a half-copied duplicate behind; the original is intact.
+    if to.is_dir() {
+      fs::remove_dir_all(to).ok();
+    } else {
+      fs::remove_file(to).ok();
+    }
+  }
+  copied
+}
+
+/// Moves everything in `from` into `to`, calling `on_progress(done, total)`
+/// after each entry. Entries that already exist in `to` are left alone and
+/// reported, as are any that fail to move.
+pub fn move_dir_contents(
+  from: &Path,
+  to: &Path,
+  mut on_progress: impl FnMut(usize, usize),
+) -> MoveReport {
+  let mut report = MoveReport {
+    moved: 0,
+    failures: Vec::new(),
+  };
+  let entries: Vec<_> = match fs::read_dir(from) {
+    Ok(entries) => entries.flatten().collect(),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return report,
+    Err(e) => {
+      report.failures.push(format!("{}: {e}", from.display()));
+      return report;
+    }
+  };
+  let total = entries.len();
+  for (i, entry) in entries.iter().enumerate() {
+    let src = entry.path();
+    let dest = to.join(entry.file_name());
+    if dest.exists() {
+      report.failures.push(format!("{}: already exists in the new folder", dest.display()));
+    } else {
+      match move_entry(&src, &dest) {
+        Ok(()) => report.moved += 1,
+        Err(e) => report.failures.push(format!("{}: {e}", src.display())),
+      }
+    }
+    on_progress(i + 1, total);
+  }
+  report
+}
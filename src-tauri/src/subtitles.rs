@@ -0,0 +1,74 @@
+use anyhow::{anyhow, Result};
+use std::{
+  fmt::Write as _,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use crate::transcribe::{read_transcript, Segment};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+  Srt,
+  Vtt,
+}
+
+impl SubtitleFormat {
+  pub fn parse(format: &str) -> Result<Self> {
+    match format.trim().to_lowercase().as_str() {
+      "srt" => Ok(Self::Srt),
+      "vtt" => Ok(Self::Vtt),
+      other => Err(anyhow!("unknown subtitle format '{other}'; use 'srt' or 'vtt'")),
+    }
+  }
+
+  fn extension(self) -> &'static str {
+    match self {
+      Self::Srt => "srt",
+      Self::Vtt => "vtt",
+    }
+  }
+}
+
+// HH:MM:SS,mmm for SRT, HH:MM:SS.mmm for WebVTT.
+fn timestamp(secs: f64, format: SubtitleFormat) -> String {
+  let total_ms = (secs.max(0.0) * 1000.0).round() as u64;
+  let (h, m, s, ms) = (
+    total_ms / 3_600_000,
+    total_ms / 60_000 % 60,
+    total_ms / 1000 % 60,
+    total_ms % 1000,
+  );
+  let sep = if format == SubtitleFormat::Srt { ',' } else { '.' };
+  format!("{h:02}:{m:02}:{s:02}{sep}{ms:03}")
+}
+
+pub fn render(segments: &[Segment], format: SubtitleFormat) -> String {
+  let mut out = String::new();
+  if format == SubtitleFormat::Vtt {
+    out.push_str("WEBVTT\n\n");
+  }
+  for (i, seg) in segments.iter().enumerate() {
+    if format == SubtitleFormat::Srt {
+      writeln!(out, "{}", i + 1).ok();
+    }
+    writeln!(
+      out,
+      "{} --> {}\n{}\n",
+      timestamp(seg.start, format),
+      timestamp(seg.end, format),
+      seg.text
+    )
+    .ok();
+  }
+  out
+}
+
+/// Writes `<session_id>.srt` / `.vtt` next to the recording from its saved
+/// transcript and returns the path.
+pub fn export_transcript(dir: &Path, session_id: &str, format: SubtitleFormat) -> Result<PathBuf> {
+  let transcript = read_transcript(dir, session_id)?;
+  let path = dir.join(format!("{session_id}.{}", format.extension()));
+  fs::write(&path, render(&transcript.segments, format))?;
+  Ok(path)
+}
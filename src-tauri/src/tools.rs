@@ -0,0 +1,66 @@
+use anyhow::{anyhow, Result};
+use std::{
+  env,
+  ffi::OsStr,
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+// GUI apps often don't inherit the shell's PATH (notably on macOS), so also
+// look where package managers usually put things.
+fn extra_dirs() -> Vec<PathBuf> {
+  let mut dirs = Vec::new();
+  if cfg!(windows) {
+    if let Some(local) = dirs::data_local_dir() {
+      dirs.push(local.join("Microsoft").join("WinGet").join("Links"));
+    }
+    if let Some(home) = dirs::home_dir() {
+      dirs.push(home.join("scoop").join("shims"));
+    }
+    dirs.push(PathBuf::from(r"C:\ProgramData\chocolatey\bin"));
+    dirs.push(PathBuf::from(r"C:\Program Files\Tesseract-OCR"));
+  } else {
+    dirs.push(PathBuf::from("/opt/homebrew/bin"));
+    dirs.push(PathBuf::from("/usr/local/bin"));
+    dirs.push(PathBuf::from("/usr/bin"));
+    if let Some(home) = dirs::home_dir() {
+      dirs.push(home.join(".local").join("bin"));
+    }
+  }
+  dirs
+}
+
+/// Full path to an external program like `ffmpeg` or `yt-dlp`, searching
+/// PATH first and then common install locations.
+pub fn find_tool(name: &str) -> Option<PathBuf> {
+  let file = if cfg!(windows) { format!("{name}.exe") } else { name.to_string() };
+  let path_dirs = env::var_os("PATH")
+    .map(|p| env::split_paths(&p).collect::<Vec<_>>())
+    .unwrap_or_default();
+  path_dirs
+    .into_iter()
+    .chain(extra_dirs())
+    .map(|d| d.join(&file))
+    .find(|p| p.is_file())
+}
+
+/// Like `find_tool`, but with an error telling the user how to get it.
+pub fn require_tool(name: &str, install_hint: &str) -> Result<PathBuf> {
+  find_tool(name).ok_or_else(|| anyhow!("{name} is not installed or not on PATH; {install_hint}"))
+}
+
+/// Runs a tool to completion and returns its stdout, or its stderr as the
+/// error if it exits non-zero.
+pub fn run_tool<I, S>(program: &Path, args: I) -> Result<Vec<u8>>
+where
+  I: IntoIterator<Item = S>,
+  S: AsRef<OsStr>,
+{
+  let out = Command::new(program).args(args).output()?;
+  if !out.status.success() {
+    let name = program.file_stem().and_then(|s| s.to_str()).unwrap_or("tool");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    return Err(anyhow!("{name} failed: {}", stderr.trim()));
+  }
+  Ok(out.stdout)
+}
@@ -0,0 +1,490 @@
+use anyhow::{anyhow, Result};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  thread,
+};
+use tauri::{AppHandle, Emitter};
+use whisper_rs::{
+  get_lang_id, get_lang_str, FullParams, SamplingStrategy, WhisperContext,
+  WhisperContextParameters, WhisperState,
+};
+
+use crate::audio::{load_for_whisper, FrameResampler, WHISPER_RATE};
+use crate::sessions::{find_recording, list_sessions, validate_session_id, write_json_atomic};
+use crate::storage::app_data_dir;
+
+const DEFAULT_MODEL: &str = "base";
+// Whisper's native window. Chunks are cut at the quietest point within the
+// last CUT_SEARCH_SECS of each window, so a cut lands between words instead
+// of through one; chunks never overlap, so nothing is transcribed twice.
+const CHUNK_SECS: f64 = 30.0;
+const CUT_SEARCH_SECS: f64 = 3.0;
+const CUT_FRAME_SECS: f64 = 0.02;
+
+// Live transcription re-runs Whisper over the open window every
+// LIVE_STEP_SECS of new audio, and commits the window (cut at a quiet
+// point, as above) once it reaches LIVE_WINDOW_SECS.
+const LIVE_STEP_SECS: f64 = 3.0;
+const LIVE_WINDOW_SECS: f64 = 20.0;
+
+pub const SEGMENT_EVENT: &str = "transcribe://segment";
+pub const PROGRESS_EVENT: &str = "transcribe://progress";
+pub const LIVE_EVENT: &str = "transcribe://live";
+
+/// Payload of `transcribe://progress`, sent once per finished chunk; at
+/// ~30 s of audio per chunk that is already sparse enough for the UI.
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress {
+  // Share of the audio done, 0-100.
+  pub percent: f32,
+  // 0-based index of the chunk that just finished.
+  pub chunk_index: usize,
+  pub chunk_count: usize,
+}
+
+/// Shared stop flag for the running transcription, checked between chunks
+/// and by whisper.cpp's abort callback.
+/// Cloning shares the flag, like the recorder's control channel sender.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn reset(&self) {
+    self.0.store(false, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+}
+
+/// What a finished transcription hands back. `text` is the segments joined
+/// with spaces; `language` is the ISO 639-1 code that was used, detected by
+/// Whisper when none was forced. Saved as `<session_id>.transcript.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcript {
+  pub text: String,
+  pub segments: Vec<Segment>,
+  pub language: Option<String>,
+  // Stopped early via CancelToken; `segments` holds what finished before.
+  #[serde(skip)]
+  pub cancelled: bool,
+}
+
+/// Payload of `transcribe://live`. A `partial` result is a fresh guess at the
+/// audio still in the open window and replaces the previous partial; a final
+/// one (`partial: false`) won't change again and is appended.
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveEvent {
+  pub session_id: String,
+  pub segments: Vec<Segment>,
+  pub partial: bool,
+}
+
+/// Checks a user-facing language choice. `None`/"auto" means detect;
+/// anything else must be a code whisper.cpp knows ("en", "es", ...).
+pub fn parse_language(language: Option<&str>) -> Result<Option<String>> {
+  match language.map(|l| l.trim().to_lowercase()) {
+    None => Ok(None),
+    Some(l) if l.is_empty() || l == "auto" => Ok(None),
+    // Full names ("english") are accepted too; always hand back the code.
+    Some(l) => match get_lang_id(&l).and_then(get_lang_str) {
+      Some(code) => Ok(Some(code.to_string())),
+      None => Err(anyhow!(
+        "unknown transcription language '{l}'; use an ISO 639-1 code or 'auto'"
+      )),
+    },
+  }
+}
+
+/// Where the audio gets transcribed: whisper.cpp on this machine, or the
+/// OpenAI `audio/transcriptions` endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+  Local,
+  OpenAi,
+}
+
+impl Backend {
+  pub fn parse(backend: Option<&str>) -> Result<Self> {
+    match backend.map(|b| b.trim().to_lowercase()).as_deref() {
+      None | Some("") | Some("local") => Ok(Self::Local),
+      Some("openai") => Ok(Self::OpenAi),
+      Some(other) => Err(anyhow!(
+        "unknown transcription backend '{other}'; use 'local' or 'openai'"
+      )),
+    }
+  }
+}
+
+/// One piece of transcript, with offsets in seconds from the start of the
+/// recording. Also the payload of `transcribe://segment`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Segment {
+  pub start: f64,
+  pub end: f64,
+  pub text: String,
+}
+
+pub fn transcript_path(dir: &Path, session_id: &str) -> PathBuf {
+  dir.join(format!("{session_id}.transcript.json"))
+}
+
+pub fn write_transcript(dir: &Path, session_id: &str, transcript: &Transcript) -> Result<()> {
+  write_json_atomic(&transcript_path(dir, session_id), transcript)
+}
+
+/// The saved transcript for a session, or an error telling the user to
+/// transcribe it first.
+pub fn read_transcript(dir: &Path, session_id: &str) -> Result<Transcript> {
+  validate_session_id(session_id)?;
+  let path = transcript_path(dir, session_id);
+  if !path.exists() {
+    return Err(anyhow!("session '{session_id}' has not been transcribed yet; transcribe it first"));
+  }
+  Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+/// GGML models live in the app data dir, not in Downloads with recordings.
+pub fn models_dir() -> PathBuf {
+  app_data_dir().join("models")
+}
+
+/// `model` may be a path to a GGML file, or a short name like "base" or
+/// "small.en" that maps to `<models_dir>/ggml-<name>.bin`.
+pub fn resolve_model_path(model: Option<&str>) -> PathBuf {
+  let model = model.unwrap_or(DEFAULT_MODEL);
+  let as_path = Path::new(model);
+  if as_path.is_file() {
+    return as_path.to_path_buf();
+  }
+  models_dir().join(format!("ggml-{model}.bin"))
+}
+
+/// A GGML file in the models folder. `name` is what `resolve_model_path`
+/// takes ("base" for ggml-base.bin).
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledModel {
+  pub name: String,
+  pub file_name: String,
+  pub size_bytes: u64,
+}
+
+/// What's in the models folder, and whether the model transcription would
+/// use is there.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelStatus {
+  pub models_dir: String,
+  pub installed: Vec<InstalledModel>,
+  // As passed in, or the default.
+  pub selected: String,
+  pub selected_path: String,
+  pub selected_installed: bool,
+}
+
+/// Lists installed models, sorted by name. A missing folder just means
+/// none are installed yet.
+pub fn model_status(model: Option<&str>) -> Result<ModelStatus> {
+  let dir = models_dir();
+  let mut installed = Vec::new();
+  if dir.is_dir() {
+    for entry in fs::read_dir(&dir)?.flatten() {
+      let Some(file_name) = entry.file_name().to_str().map(str::to_string) else { continue };
+      let Some(name) = file_name.strip_prefix("ggml-").and_then(|n| n.strip_suffix(".bin"))
+      else {
+        continue;
+      };
+      let Ok(meta) = entry.metadata() else { continue };
+      if !meta.is_file() {
+        continue;
+      }
+      let name = name.to_string();
+      installed.push(InstalledModel { name, file_name, size_bytes: meta.len() });
+    }
+  }
+  installed.sort_by(|a, b| a.name.cmp(&b.name));
+  let selected_path = resolve_model_path(model);
+  Ok(ModelStatus {
+    models_dir: dir.to_string_lossy().to_string(),
+    installed,
+    selected: model.unwrap_or(DEFAULT_MODEL).to_string(),
+    selected_installed: selected_path.is_file(),
+    selected_path: selected_path.to_string_lossy().to_string(),
+  })
+}
+
+/// The WAV for `session_id`, or the newest session when none is given.
+pub fn resolve_session_wav(dir: &Path, session_id: Option<&str>) -> Result<PathBuf> {
+  match session_id {
+    Some(id) => {
+      validate_session_id(id)?;
+      find_recording(dir, id).ok_or_else(|| anyhow!("no recording found for session '{id}'"))
+    }
+    None => list_sessions(dir)?
+      .into_iter()
+      .next()
+      .map(|s| PathBuf::from(s.wav_path))
+      .ok_or_else(|| anyhow!("no recordings to transcribe")),
+  }
+}
+
+/// Runs whisper.cpp over a WAV in ~30 s chunks, calling `on_segment` as each
+/// segment is finished. `language` comes from `parse_language`; when it is
+/// `None` the first chunk's detected language is used for the rest.
+/// Cancelling returns the chunks finished so far with `cancelled` set.
+pub fn transcribe_wav(
+  wav: &Path,
+  model_path: &Path,
+  language: Option<String>,
+  cancel: &CancelToken,
+  mut on_segment: impl FnMut(&Segment),
+  mut on_progress: impl FnMut(&Progress),
+) -> Result<Transcript> {
+  if !model_path.is_file() {
+    return Err(anyhow!(
+      "whisper model not found at {}; download a GGML model (e.g. ggml-base.bin) into {}",
+      model_path.display(),
+      models_dir().display()
+    ));
+  }
+  let audio = load_for_whisper(wav)?;
+
+  let ctx = WhisperContext::new_with_params(model_path, WhisperContextParameters::default())
+    .map_err(|e| anyhow!("failed to load whisper model: {e}"))?;
+  let mut state = ctx.create_state()?;
+  let threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+
+  let mut segments = Vec::new();
+  let mut language = language;
+  let mut cancelled = false;
+  let chunks = chunk_bounds(&audio);
+  for (chunk_index, &(start, end)) in chunks.iter().enumerate() {
+    if cancel.is_cancelled() {
+      cancelled = true;
+      break;
+    }
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(Some(language.as_deref().unwrap_or("auto")));
+    params.set_n_threads(threads as i32);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_special(false);
+    params.set_print_timestamps(false);
+
+    // Lets whisper.cpp bail out mid-chunk too; that chunk's output is dropped.
+    let abort = cancel.clone();
+    params.set_abort_callback_safe(move || abort.is_cancelled());
+
+    if let Err(e) = state.full(params, &audio[start..end]) {
+      if cancel.is_cancelled() {
+        cancelled = true;
+        break;
+      }
+      return Err(e.into());
+    }
+    if language.is_none() {
+      language = get_lang_str(state.full_lang_id_from_state()).map(str::to_string);
+    }
+
+    let offset = start as f64 / WHISPER_RATE as f64;
+    for seg in collect_segments(&state, offset)? {
+      on_segment(&seg);
+      segments.push(seg);
+    }
+    on_progress(&Progress {
+      percent: end as f32 / audio.len() as f32 * 100.0,
+      chunk_index,
+      chunk_count: chunks.len(),
+    });
+  }
+  let text = segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" ");
+  Ok(Transcript {
+    text,
+    segments,
+    language,
+    cancelled,
+  })
+}
+
+// The segments of the last `full` run, shifted by `offset` seconds.
+fn collect_segments(state: &WhisperState, offset: f64) -> Result<Vec<Segment>> {
+  let mut out = Vec::new();
+  for segment in state.as_iter() {
+    let text = segment.to_str_lossy()?.trim().to_string();
+    if text.is_empty() {
+      continue;
+    }
+    // Whisper timestamps are in centiseconds, relative to the chunk.
+    out.push(Segment {
+      start: offset + segment.start_timestamp() as f64 / 100.0,
+      end: offset + segment.end_timestamp() as f64 / 100.0,
+      text,
+    });
+  }
+  Ok(out)
+}
+
+/// Transcribes a recording as it happens. The recorder pushes what it
+/// writes, a mono frame at a time, and results arrive as `transcribe://live`
+/// events. Whisper runs on its own thread, so a slow model never holds up
+/// capture; it just updates less often. Dropping this ends the stream: the
+/// last of the audio is transcribed and the thread exits on its own.
+pub struct LiveTranscriber {
+  tx: Sender<Vec<f32>>,
+  resampler: FrameResampler,
+  pending: Vec<f32>,
+}
+
+impl LiveTranscriber {
+  /// `offset_secs` is how much audio the recording already has (a resumed
+  /// session), so segment times line up with the file.
+  pub fn start(
+    app: AppHandle,
+    session_id: String,
+    sample_rate: u32,
+    offset_secs: f64,
+  ) -> Result<Self> {
+    let model = resolve_model_path(None);
+    if !model.is_file() {
+      return Err(anyhow!("whisper model not found at {}", model.display()));
+    }
+    let (tx, rx) = unbounded::<Vec<f32>>();
+    thread::Builder::new().name("live-transcribe".into()).spawn(move || {
+      if let Err(e) = run_live(&app, &session_id, &model, offset_secs, rx) {
+        tracing::warn!("live transcription of {session_id} stopped: {e}");
+      }
+    })?;
+    Ok(Self {
+      tx,
+      resampler: FrameResampler::new(sample_rate, WHISPER_RATE),
+      pending: Vec::new(),
+    })
+  }
+
+  pub fn push_frame(&mut self, mono: f32) {
+    self.resampler.push(&[mono], &mut self.pending);
+  }
+
+  /// Hands everything pushed since the last flush to the Whisper thread.
+  pub fn flush(&mut self) {
+    if !self.pending.is_empty() {
+      self.tx.send(std::mem::take(&mut self.pending)).ok();
+    }
+  }
+}
+
+fn run_live(
+  app: &AppHandle,
+  session_id: &str,
+  model: &Path,
+  offset_secs: f64,
+  rx: Receiver<Vec<f32>>,
+) -> Result<()> {
+  let ctx = WhisperContext::new_with_params(model, WhisperContextParameters::default())
+    .map_err(|e| anyhow!("failed to load whisper model: {e}"))?;
+  let mut state = ctx.create_state()?;
+  let threads = thread::available_parallelism().map_or(4, |n| n.get());
+  let rate = WHISPER_RATE as f64;
+  let (step, window) = ((LIVE_STEP_SECS * rate) as usize, (LIVE_WINDOW_SECS * rate) as usize);
+  let (search, frame) = ((CUT_SEARCH_SECS * rate) as usize, (CUT_FRAME_SECS * rate) as usize);
+
+  let mut language: Option<String> = None;
+  // Audio not yet committed, starting `offset` seconds into the recording.
+  let mut buf: Vec<f32> = Vec::new();
+  let mut offset = offset_secs;
+  // buf.len() at the last partial pass.
+  let mut last_pass = 0;
+  let mut open = true;
+  while open {
+    // Wait for audio, then take everything queued: a model slower than real
+    // time skips partial passes instead of falling further behind.
+    match rx.recv() {
+      Ok(block) => buf.extend(block),
+      Err(_) => open = false,
+    }
+    buf.extend(rx.try_iter().flatten());
+
+    let mut run = |audio: &[f32], offset: f64| -> Result<Vec<Segment>> {
+      let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+      params.set_language(Some(language.as_deref().unwrap_or("auto")));
+      params.set_n_threads(threads as i32);
+      params.set_print_progress(false);
+      params.set_print_realtime(false);
+      params.set_print_special(false);
+      params.set_print_timestamps(false);
+      state.full(params, audio)?;
+      if language.is_none() {
+        language = get_lang_str(state.full_lang_id_from_state()).map(str::to_string);
+      }
+      collect_segments(&state, offset)
+    };
+    // Commit full windows, and whatever is left once the recording ends.
+    while buf.len() >= window || (!open && !buf.is_empty()) {
+      let cut = if buf.len() >= window {
+        quietest_cut(&buf, window - search, window, frame)
+      } else {
+        buf.len()
+      };
+      let segments = run(&buf[..cut], offset)?;
+      let event = LiveEvent { session_id: session_id.to_string(), segments, partial: false };
+      app.emit(LIVE_EVENT, event).ok();
+      buf.drain(..cut);
+      offset += cut as f64 / rate;
+      last_pass = 0;
+    }
+    if open && buf.len() >= last_pass + step {
+      let segments = run(&buf, offset)?;
+      let event = LiveEvent { session_id: session_id.to_string(), segments, partial: true };
+      app.emit(LIVE_EVENT, event).ok();
+      last_pass = buf.len();
+    }
+  }
+  Ok(())
+}
+
+// Splits 16 kHz audio into back-to-back [start, end) ranges of at most
+// CHUNK_SECS, each ending at the quietest short frame near its tail.
+fn chunk_bounds(audio: &[f32]) -> Vec<(usize, usize)> {
+  let rate = WHISPER_RATE as f64;
+  let chunk = (CHUNK_SECS * rate) as usize;
+  let search = (CUT_SEARCH_SECS * rate) as usize;
+  let frame = (CUT_FRAME_SECS * rate) as usize;
+
+  let mut bounds = Vec::new();
+  let mut start = 0;
+  while start < audio.len() {
+    let hard_end = (start + chunk).min(audio.len());
+    let end = if hard_end == audio.len() {
+      hard_end
+    } else {
+      quietest_cut(audio, hard_end - search, hard_end, frame)
+    };
+    bounds.push((start, end));
+    start = end;
+  }
+  bounds
+}
+
+fn quietest_cut(audio: &[f32], from: usize, to: usize, frame: usize) -> usize {
+  let mut best = (f32::MAX, to);
+  let mut pos = from;
+  while pos + frame <= to {
+    let energy: f32 = audio[pos..pos + frame].iter().map(|s| s * s).sum();
+    if energy < best.0 {
+      best = (energy, pos + frame / 2);
+    }
+    pos += frame;
+  }
+  best.1
+}
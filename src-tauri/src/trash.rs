@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+use crate::recorder::TEMP_DIR;
+use crate::sessions::{read_json_or_default, session_files, write_json_atomic};
+use crate::storage::{clear_dir_contents, ClearSummary};
+
+pub const TRASH_DIR: &str = ".trash";
+const MANIFEST: &str = "manifest.json";
+
+/// One trashed file or folder. Paths are relative (to the storage dir and
+/// the trash dir), so the trash survives the storage folder being moved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+  // The `<id>` prefix of session files; None for anything else.
+  pub session_id: Option<String>,
+  pub original: String,
+  pub trashed: String,
+  // RFC 3339.
+  pub deleted_at: String,
+}
+
+pub fn trash_dir(dir: &Path) -> PathBuf {
+  dir.join(TRASH_DIR)
+}
+
+fn manifest_path(dir: &Path) -> PathBuf {
+  trash_dir(dir).join(MANIFEST)
+}
+
+pub fn read_manifest(dir: &Path) -> Vec<TrashEntry> {
+  read_json_or_default(&manifest_path(dir))
+}
+
+// Moves top-level `entries` of `dir` into a fresh `.trash/<millis>/` batch
+// and records them. Returns (moved, failures).
+fn trash_entries(dir: &Path, entries: &[PathBuf]) -> Result<(Vec<TrashEntry>, Vec<String>)> {
+  let now = chrono::Local::now();
+  let batch = now.timestamp_millis().to_string();
+  let batch_dir = trash_dir(dir).join(&batch);
+  fs::create_dir_all(&batch_dir)?;
+
+  let mut manifest = read_manifest(dir);
+  let mut moved = Vec::new();
+  let mut failures = Vec::new();
+  for path in entries {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else { continue };
+    match fs::rename(path, batch_dir.join(name)) {
+      Ok(()) => moved.push(TrashEntry {
+        session_id: name.split_once('.').map(|(id, _)| id.to_string()),
+        original: name.to_string(),
+        trashed: format!("{batch}/{name}"),
+        deleted_at: now.to_rfc3339(),
+      }),
+      Err(e) => failures.push(format!("{}: {e}", path.display())),
+    }
+  }
+  manifest.extend(moved.iter().cloned());
+  write_json_atomic(&manifest_path(dir), &manifest)?;
+  Ok((moved, failures))
+}
+
+/// Moves one session's files to the trash; returns their original paths.
+pub fn trash_session(dir: &Path, session_id: &str) -> Result<Vec<String>> {
+  let files = session_files(dir, session_id)?;
+  if files.is_empty() {
+    return Err(anyhow!("session '{session_id}' not found"));
+  }
+  let (moved, failures) = trash_entries(dir, &files)?;
+  if let Some(first) = failures.first() {
+    return Err(anyhow!("couldn't move to trash: {first}"));
+  }
+  Ok(moved.iter().map(|e| dir.join(&e.original).to_string_lossy().to_string()).collect())
+}
+
+/// Moves everything in `dir` into the trash, except the trash itself and
+/// `.tmp`, where a recording may still be writing.
+pub fn trash_all(dir: &Path) -> ClearSummary {
+  let mut summary = ClearSummary {
+    removed: 0,
+    failures: Vec::new(),
+  };
+  let entries: Vec<PathBuf> = match fs::read_dir(dir) {
+    Ok(entries) => entries
+      .flatten()
+      .filter(|e| e.file_name() != TRASH_DIR && e.file_name() != TEMP_DIR)
+      .map(|e| e.path())
+      .collect(),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return summary,
+    Err(e) => {
+      summary.failures.push(format!("{}: {e}", dir.display()));
+      return summary;
+    }
+  };
+  if entries.is_empty() {
+    return summary;
+  }
+  match trash_entries(dir, &entries) {
+    Ok((moved, failures)) => {
+      summary.removed = moved.len();
+      summary.failures = failures;
+    }
+    Err(e) => summary.failures.push(e.to_string()),
+  }
+  summary
+}
+
+/// Puts a trashed session's files back where they were. Refuses if a file
+/// with the same name has been created since. A session trashed more than
+/// once (deleted, recreated under the same id, deleted again) gets its
+/// latest deletion back; older ones stay in the trash.
+pub fn restore_session(dir: &Path, session_id: &str) -> Result<Vec<String>> {
+  let mut manifest = read_manifest(dir);
+  let of_session = |e: &TrashEntry| e.session_id.as_deref() == Some(session_id);
+  let latest = manifest
+    .iter()
+    .filter(|e| of_session(e))
+    .map(batch_of)
+    .max()
+    .ok_or_else(|| anyhow!("session '{session_id}' is not in the trash"))?;
+  let restore: Vec<TrashEntry> = manifest
+    .iter()
+    .filter(|e| of_session(e) && batch_of(e) == latest)
+    .cloned()
+    .collect();
+  if let Some(clash) = restore.iter().find(|e| dir.join(&e.original).exists()) {
+    return Err(anyhow!(
+      "can't restore: {} already exists; rename that session first",
+      clash.original
+    ));
+  }
+  let mut restored = Vec::new();
+  for entry in &restore {
+    let dest = dir.join(&entry.original);
+    // Checked again right before the move, since rename would replace it.
+    if dest.exists() {
+      return Err(anyhow!("can't restore: {} already exists", entry.original));
+    }
+    fs::rename(trash_dir(dir).join(&entry.trashed), &dest)?;
+    // Saved after every file, so a failure partway leaves the manifest
+    // listing exactly what is still in the trash.
+    manifest.retain(|e| e.trashed != entry.trashed);
+    write_json_atomic(&manifest_path(dir), &manifest)?;
+    restored.push(dest.to_string_lossy().to_string());
+  }
+  Ok(restored)
+}
+
+// The `<millis>` batch an entry was trashed in; later deletions sort higher.
+fn batch_of(entry: &TrashEntry) -> u64 {
+  entry
+    .trashed
+    .split_once('/')
+    .and_then(|(batch, _)| batch.parse().ok())
+    .unwrap_or(0)
+}
+
+/// Permanently deletes everything in the trash.
+pub fn empty_trash(dir: &Path) -> ClearSummary {
+  clear_dir_contents(&trash_dir(dir))
+}
@@ -0,0 +1,142 @@
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+use serde::Serialize;
+use std::{
+  path::{Path, PathBuf},
+  time::{Duration, Instant},
+};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+
+use crate::imports::register_saved_audio;
+use crate::recorder::new_session_id;
+use crate::sessions::reserve_stem;
+use crate::tools::require_tool;
+
+pub const PROGRESS_EVENT: &str = "import://youtube-progress";
+pub const DONE_EVENT: &str = "import://youtube-done";
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+// Machine-readable progress lines on stdout: "<marker> <percent>% <eta secs>".
+const PROGRESS_MARKER: &str = "applesauce-progress";
+
+/// Payload of `import://youtube-progress`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+  pub percent: f32,
+  // None while yt-dlp can't estimate it yet.
+  pub eta_secs: Option<u64>,
+}
+
+/// Payload of `import://youtube-done`, sent once whether it worked or not.
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadDone {
+  pub path: Option<String>,
+  pub error: Option<String>,
+}
+
+const YOUTUBE_HOSTS: [&str; 5] = [
+  "youtube.com",
+  "www.youtube.com",
+  "m.youtube.com",
+  "music.youtube.com",
+  "youtu.be",
+];
+
+/// Accepts http(s) links to a YouTube video; returns the normalised URL.
+pub fn validate_url(url: &str) -> Result<String> {
+  let parsed = Url::parse(url.trim()).map_err(|_| anyhow!("not a valid URL: {url}"))?;
+  let host = parsed.host_str().unwrap_or_default().to_lowercase();
+  if !matches!(parsed.scheme(), "http" | "https") || !YOUTUBE_HOSTS.contains(&host.as_str()) {
+    return Err(anyhow!("not a YouTube link: {url}"));
+  }
+  Ok(parsed.to_string())
+}
+
+// yt-dlp prints a lot; the "ERROR: ..." lines are what the user needs
+// (private, age-restricted, unavailable, ...).
+fn error_summary(stderr: &[u8]) -> String {
+  let stderr = String::from_utf8_lossy(stderr);
+  let errors: Vec<&str> = stderr.lines().filter(|l| l.starts_with("ERROR:")).collect();
+  if errors.is_empty() {
+    stderr.trim().to_string()
+  } else {
+    errors.join("\n")
+  }
+}
+
+fn parse_progress(line: &str) -> Option<DownloadProgress> {
+  let mut parts = line.trim().strip_prefix(PROGRESS_MARKER)?.split_whitespace();
+  let percent = parts.next()?.trim_end_matches('%').parse().ok()?;
+  let eta_secs = parts.next().and_then(|e| e.parse().ok());
+  Some(DownloadProgress { percent, eta_secs })
+}
+
+/// Downloads a video's audio as `<out_dir>/<session_id>.wav` via yt-dlp
+/// (which needs ffmpeg for the conversion) run through the shell plugin, and
+/// writes its sidecar so it lists as a session. Streams `import://youtube-progress` while it runs and finishes with one
+/// `import://youtube-done`.
+pub async fn download_audio(app: &AppHandle, url: &str, out_dir: &Path) -> Result<PathBuf> {
+  let result = run_download(app, url, out_dir).await;
+  let done = match &result {
+    Ok(path) => DownloadDone { path: Some(path.to_string_lossy().to_string()), error: None },
+    Err(e) => DownloadDone { path: None, error: Some(e.to_string()) },
+  };
+  app.emit(DONE_EVENT, done).ok();
+  result
+}
+
+async fn run_download(app: &AppHandle, url: &str, out_dir: &Path) -> Result<PathBuf> {
+  let url = validate_url(url)?;
+  let yt_dlp = require_tool(
+    "yt-dlp",
+    "install it from https://github.com/yt-dlp/yt-dlp (and ffmpeg) to import from YouTube",
+  )?;
+  std::fs::create_dir_all(out_dir)?;
+  // Held until the sidecar is written, so nothing else takes the id while
+  // yt-dlp is still downloading.
+  let reserved = reserve_stem(out_dir, &new_session_id())?;
+  let wav = out_dir.join(format!("{}.wav", reserved.id));
+  let template = wav.with_extension("%(ext)s");
+
+  let progress_template =
+    format!("download:{PROGRESS_MARKER} %(progress._percent_str)s %(progress.eta)s");
+  let (mut rx, _child) = app
+    .shell()
+    .command(yt_dlp)
+    .args(["--no-playlist", "--newline", "-x", "--audio-format", "wav"])
+    .args(["--progress-template", progress_template.as_str(), "-o"])
+    .arg(&template)
+    .arg(&url)
+    .spawn()?;
+
+  let mut stderr = Vec::new();
+  let mut exit_code = None;
+  let mut last_emit: Option<Instant> = None;
+  while let Some(event) = rx.recv().await {
+    match event {
+      CommandEvent::Stdout(line) => {
+        let Some(progress) = parse_progress(&String::from_utf8_lossy(&line)) else { continue };
+        let due = last_emit.is_none_or(|t| t.elapsed() >= PROGRESS_INTERVAL);
+        if due || progress.percent >= 100.0 {
+          last_emit = Some(Instant::now());
+          app.emit(PROGRESS_EVENT, progress).ok();
+        }
+      }
+      CommandEvent::Stderr(line) => {
+        stderr.extend_from_slice(&line);
+        stderr.push(b'\n');
+      }
+      CommandEvent::Terminated(t) => exit_code = t.code,
+      _ => {}
+    }
+  }
+  if exit_code != Some(0) {
+    return Err(anyhow!("yt-dlp failed: {}", error_summary(&stderr)));
+  }
+  if !wav.is_file() {
+    return Err(anyhow!("yt-dlp finished but {} was not created", wav.display()));
+  }
+  register_saved_audio(out_dir, &reserved.id, &wav)?;
+  Ok(wav)
+}
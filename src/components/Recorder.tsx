@@ -158,11 +158,11 @@ export default function Recorder() {
           : "webm";
 
         try {
-          const savedPath = await invoke<string>("save_audio_base64", {
+          const saved = await invoke<{ path: string }>("save_audio_base64", {
             base64Data: base64,
             extHint: ext,
           });
-          setSavingPath(savedPath);
+          setSavingPath(saved.path);
         } catch (e: any) {
           console.error("Save error:", e);
           setSavingPath(`Save failed: ${String(e)}`);
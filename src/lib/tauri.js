@@ -32,36 +32,245 @@ async function tauriInvoke(cmd, args = {}) {
 /*                App-specific commands (match Rust names)             */
 /* ------------------------------------------------------------------ */
 
-/** Recording controls (Rust: *_cmd; no struct args) */
-export const startRecording  = () => tauriInvoke("start_recording_cmd", {});
+/** Recording controls (Rust: *_cmd; start takes optional args) */
+// Starting while already recording resolves to that session with already_running: true
+// "recording://level" { rms, peak } (0..1) plus rms_db/peak_db in dBFS, -60..0
+// config.format: "wav" (default) | "mp3" | "flac"; stop resolves { final_path, ... }
+// config.source: "mic" (default) | "system" | "both"; system audio is Windows-only
+// config.downmix_to_mono: true saves a mono file even from a stereo-only mic
+// config.resample_to: file sample rate when the device can't capture at it
+// (e.g. 16000 from a 48 kHz-only mic)
+// config.live_transcribe: "transcribe://live" { segments, partial } while recording;
+// a partial replaces the previous partial, a final one is appended
+// config.preroll_secs: "recording://countdown" ticks before capture starts;
+// stopping during it resolves { message: "cancelled", final_path: null }
+// config.gate_threshold (RMS 0..1, off by default) turns the mic down by
+// config.gate_attenuation_db (default -60) while it's only hiss
+// deviceName/config left out reuse the last recording's (getLastRecordingConfig)
+// Refuses to start with under 200 MB free; "recording://auto-stopped" with
+// reason "low_disk" if space runs out mid-recording
+// label ("Biology Lecture 3") names the file Biology_Lecture_3.wav, with _2,
+// _3, ... if taken; the returned session_id is that name
+export const startRecording  = (deviceName, config, label) =>
+  tauriInvoke("start_recording_cmd", {
+    args: { device_name: deviceName ?? null, config: config ?? null, label: label ?? null },
+  });
+// Appends to an existing WAV session instead of starting a new file
+export const resumeSession   = (sessionId, deviceName, config) =>
+  tauriInvoke("resume_session_cmd", {
+    args: { session_id: sessionId, device_name: deviceName ?? null, config: config ?? null },
+  });
+// "recording://tick" { elapsed_secs, paused } arrives once a second for the timer
 export const pauseRecording  = () => tauriInvoke("pause_recording_cmd", {});
 export const resumeRecording = () => tauriInvoke("resume_recording_cmd", {});
+// Resolves with clipping_pct; over 0.1% also fires "recording://clipping-warning"
+// Before settings.min_duration_secs: fails with "[too-short] ..." and keeps
+// recording, or with settings.discard_short resolves { message: "cancelled" }
 export const stopRecording   = () => tauriInvoke("stop_recording_cmd", {});
+// Stops without saving: a new recording's file is deleted, a resumed one keeps
+// only what it had before
+export const cancelRecording = () => tauriInvoke("cancel_recording_cmd", {});
+// Listen for "recording://state" { state: "recording" | "paused" | "stopped" |
+// "idle", session_id, elapsed_secs } rather than tracking the responses above;
+// it's sent after every start/pause/resume/stop, including auto-stops
+// For a single hotkey: starts if idle, stops if recording. Resolves the start
+// or stop response plus action: "started" | "stopped"
+export const toggleRecording = (deviceName, config, label) =>
+  tauriInvoke("toggle_recording_cmd", {
+    args: { device_name: deviceName ?? null, config: config ?? null, label: label ?? null },
+  });
+export const recordingStatus = () => tauriInvoke("recording_status_cmd", {});
+// Resolves to the marker's approximate time; exact times land in the session .json
+export const addMarker       = (label) =>
+  tauriInvoke("add_marker_cmd", { args: { label: label ?? null } });
+// Live software gain (also config.input_gain_db at start); clamped to -20..20 dB
+export const setInputGain    = (gainDb) =>
+  tauriInvoke("set_input_gain_cmd", { args: { gain_db: gainDb } });
+// Same list arrives as a "devices://changed" event when devices come and go;
+// "recording://device-lost" fires if the recording's mic is unplugged
+export const listInputDevices = () => tauriInvoke("list_input_devices_cmd", {});
+// Listens for `seconds` (default 3) with recording://level events, saving
+// nothing; resolves { device_name, peak, rms, signal_detected, hint }
+export const testMicrophone = (deviceName, seconds) =>
+  tauriInvoke("test_microphone_cmd", {
+    args: { device_name: deviceName ?? null, seconds: seconds ?? null },
+  });
+// { name, configs: [{ channels, min_sample_rate, max_sample_rate, sample_format }], default }
+export const deviceCapabilities = (deviceName) =>
+  tauriInvoke("device_capabilities_cmd", { args: { device_name: deviceName ?? null } });
 
 /** Frontend → Rust file save (Rust: save_audio_base64; NOT a struct param) */
-export const saveAudioBase64 = (base64Data, extHint) =>
-  tauriInvoke("save_audio_base64", { base64Data, extHint });
+// Saved as a new session in the storage folder; resolves to
+// { path, wav_path, transcode_error, register_error }. transcode: also write
+// a 16 kHz mono WAV (needs ffmpeg) so the session can be transcribed; wav_path
+// is null if not asked for or it failed (then transcode_error says why).
+// register_error is set when the session metadata couldn't be written; the
+// audio is saved but won't be listed until recoverSessions picks it up.
+// extHint must be an audio extension (webm, ogg, wav, mp3, ...); errors start
+// with "[bad-extension]" or "[too-large]" (limit: settings.max_save_mb)
+export const saveAudioBase64 = (base64Data, extHint, transcode) =>
+  tauriInvoke("save_audio_base64", { base64Data, extHint, transcode: transcode ?? false });
 
 /** Transcription (Rust signature: fn transcribe_latest_cmd(args: TranscribeArgs)) */
-export const transcribeLatest = (sessionId, model) =>
+// backend: "local" (default) | "openai"
+export const transcribeLatest = (sessionId, model, language, backend) =>
   tauriInvoke("transcribe_latest_cmd", {
-    args: { session_id: sessionId ?? null, model: model ?? null },
+    args: {
+      session_id: sessionId ?? null,
+      model: model ?? null,
+      language: language ?? null,
+      backend: backend ?? null,
+    },
+  });
+// Every session without a transcript, one after another. Resolves
+// { transcribed, failed: [[id, error]], skipped, cancelled };
+// "transcribe://batch-progress" { session_id, index, total, done, error }
+// as each starts and finishes. cancelTranscription stops the whole batch
+export const transcribeAll = (model, language, backend) =>
+  tauriInvoke("transcribe_all_cmd", {
+    args: { session_id: null, model: model ?? null, language: language ?? null, backend: backend ?? null },
+  });
+export const cancelTranscription = () => tauriInvoke("cancel_transcription_cmd", {});
+// { models_dir, installed: [{ name, file_name, size_bytes }], selected,
+//   selected_path, selected_installed } for model (default "base")
+export const whisperModelStatus = (model) =>
+  tauriInvoke("whisper_model_status_cmd", { args: { model: model ?? null } });
+// format: "srt" | "vtt"; resolves to the written file's path
+export const exportTranscript = (sessionId, format) =>
+  tauriInvoke("export_transcript_cmd", { args: { session_id: sessionId, format } });
+// Zips audio, metadata, transcript, notes etc.; destPath is a folder or a .zip path
+export const exportSessionZip = (sessionId, destPath) =>
+  tauriInvoke("export_session_zip_cmd", { args: { session_id: sessionId, dest_path: destPath } });
+// Rewrites the WAV in place; resolves { measured_lufs, gain_db, limited, ... }
+export const normalizeSession = (sessionId, targetLufs) =>
+  tauriInvoke("normalize_session_cmd", {
+    args: { session_id: sessionId, target_lufs: targetLufs ?? null },
+  });
+// Resolves [min0, max0, min1, max1, ...], channels merged
+export const waveformPeaks = (sessionId, buckets) =>
+  tauriInvoke("waveform_peaks_cmd", {
+    args: { session_id: sessionId, buckets: buckets ?? null },
+  });
+// Writes a `<id>_trimmed` copy unless inPlace; resolves { session_id, removed_*_secs, ... }
+export const trimSilence = (sessionId, thresholdDb, inPlace = false) =>
+  tauriInvoke("trim_silence_cmd", {
+    args: { session_id: sessionId, threshold_db: thresholdDb ?? null, in_place: inPlace },
+  });
+// Joins sessions in order into a new one; resolves to its session summary
+export const mergeSessions = (sessionIds, outputName, allowResample = true) =>
+  tauriInvoke("merge_sessions_cmd", {
+    args: {
+      session_ids: sessionIds,
+      output_name: outputName ?? null,
+      allow_resample: allowResample,
+    },
   });
+// Writes `<id>_part1` / `<id>_part2`; resolves to both session summaries
+export const splitSession = (sessionId, atSecs) =>
+  tauriInvoke("split_session_cmd", { args: { session_id: sessionId, at_secs: atSecs } });
+
+/** Playback (Rust: playback.rs); position via "playback://position" events */
+export const playSession     = (sessionId, startSecs) =>
+  tauriInvoke("play_session_cmd", {
+    args: { session_id: sessionId, start_secs: startSecs ?? null },
+  });
+export const pausePlayback   = () => tauriInvoke("pause_playback_cmd", {});
+export const resumePlayback  = () => tauriInvoke("resume_playback_cmd", {});
+export const seekPlayback    = (secs) => tauriInvoke("seek_playback_cmd", { args: { secs } });
+export const stopPlayback    = () => tauriInvoke("stop_playback_cmd", {});
 
 /** File imports (Rust: fn ..._cmd(args: Import...Args)) */
-export const importAudioFile    = (path) => tauriInvoke("import_audio_file_cmd",    { args: { path } });
+// Resolves { session, duplicate }; duplicate = already imported as session
+// and nothing was copied, unless force is set
+export const importAudioFile    = (path, maxSizeMb, force) =>
+  tauriInvoke("import_audio_file_cmd", {
+    args: { path, max_size_mb: maxSizeMb ?? null, force: force ?? false },
+  });
+// Resolves to one { Ok: session } or { Err: message } per path.
+export const importAudioFiles   = (paths, maxSizeMb) =>
+  tauriInvoke("import_audio_files_cmd", { args: { paths, max_size_mb: maxSizeMb ?? null } });
+export const importVideoFile    = (path) => tauriInvoke("import_video_file_cmd",    { args: { path } });
+// { format, sample_rate, channels, bits_per_sample, duration_secs, size_bytes }
+// without importing; errors start with [not-found], [unsupported] or [corrupt]
+export const audioInfo          = (path) => tauriInvoke("audio_info_cmd",           { args: { path } });
 export const importYoutubeAudio = (url)  => tauriInvoke("import_youtube_audio_cmd", { args: { url } });
-export const importPdfFile      = (path) => tauriInvoke("import_pdf_file_cmd",      { args: { path } });
+export const importPdfFile      = (path, allowOcr = false) =>
+  tauriInvoke("import_pdf_file_cmd", { args: { path, allow_ocr: allowOcr } });
+// A zip from exportSessionZip; comes back as a new session with a fresh id
+export const importSessionZip   = (path) => tauriInvoke("import_session_zip_cmd",   { args: { path } });
 
 /** Storage (Rust: *_cmd; no struct args) */
 export const openStorageDir  = () => tauriInvoke("open_storage_dir_cmd", {});
+// Newest first; resolves { sessions, offset, total }. No limit = all of them
+export const listSessions    = (offset, limit) =>
+  tauriInvoke("list_sessions_cmd", { args: { offset: offset ?? 0, limit: limit ?? null } });
+// Opens the file manager with the session's recording selected
+export const revealSession   = (sessionId) =>
+  tauriInvoke("reveal_session_cmd", { args: { session_id: sessionId } });
+// Deleting moves files to the trash; restore or empty it later.
+export const deleteSession   = (sessionId) =>
+  tauriInvoke("delete_session_cmd", { args: { session_id: sessionId } });
+export const restoreSession  = (sessionId) =>
+  tauriInvoke("restore_session_cmd", { args: { session_id: sessionId } });
+export const listTrash       = () => tauriInvoke("list_trash_cmd", {});
+// Crash recovery (also run at launch): { recovered, discarded, unreadable } ids;
+// pass true to move the unreadable ones to the trash
+export const recoverSessions = (discardUnreadable) =>
+  tauriInvoke("recover_sessions_cmd", { args: { discard_unreadable: !!discardUnreadable } });
+export const emptyTrash      = () => tauriInvoke("empty_trash_cmd", {});
+export const renameSession   = (sessionId, newName) =>
+  tauriInvoke("rename_session_cmd", { args: { session_id: sessionId, new_name: newName } });
+// Replaces the session's tags; resolves to the tags as saved
+export const setSessionTags  = (sessionId, tags) =>
+  tauriInvoke("set_session_tags_cmd", { args: { session_id: sessionId, tags } });
+// Matches name, tags and transcript text (case-insensitive); same rows as listSessions
+export const searchSessions  = (query) =>
+  tauriInvoke("search_sessions_cmd", { args: { query } });
+// [{ session_id, snippet, start_secs, end_secs }], one per matching segment
+export const searchTranscripts = (query) =>
+  tauriInvoke("search_transcripts_cmd", { args: { query } });
 export const clearStorageDir = () => tauriInvoke("clear_storage_dir_cmd", {});
+export const storageUsage    = () => tauriInvoke("storage_usage_cmd", {});
+// maxAgeDays null = use the retention setting; resolves to trashed session ids
+export const cleanupOldSessions = (maxAgeDays, dryRun = false) =>
+  tauriInvoke("cleanup_old_sessions_cmd", {
+    args: { max_age_days: maxAgeDays ?? null, dry_run: dryRun },
+  });
+// path null = reset to the default; resolves to { previous, current, moved }
+export const setStorageDir   = (path, moveExisting = false) =>
+  tauriInvoke("set_storage_dir_cmd", {
+    args: { path: path ?? null, move_existing: moveExisting },
+  });
 
 /** API key / prompt (Rust: fn ..._cmd(args: ...Args)) */
 export const saveApiKey      = (value) => tauriInvoke("save_api_key_cmd",       { args: { value } });
 export const readApiKey      = ()        => tauriInvoke("read_api_key_cmd",      {});
+export const hasApiKey       = ()        => tauriInvoke("has_api_key_cmd",       {});
+// value omitted = check the saved key; resolves to { valid, error }
+export const verifyApiKey    = (value)  =>
+  tauriInvoke("verify_api_key_cmd", { args: value == null ? null : { value } });
 export const setPromptPreset = (value)  => tauriInvoke("set_prompt_preset_cmd",  { args: { value } });
 export const getPromptPreset = ()        => tauriInvoke("get_prompt_preset_cmd", {});
 
+/** Settings (settings.json in app data; patch = partial object, null clears a key) */
+export const getSettings     = ()        => tauriInvoke("get_settings_cmd", {});
+export const updateSettings  = (patch)   => tauriInvoke("update_settings_cmd", { args: { patch } });
+// { device_name, config } the next start falls back to; nulls before the first recording
+export const getLastRecordingConfig = () => tauriInvoke("get_last_recording_config_cmd", {});
+
+/** Support: { app_version, os, storage_writable, whisper_model_present, api_key_configured, ... } */
+export const diagnostics     = ()        => tauriInvoke("diagnostics_cmd", {});
+
+/** Logs: daily files in the app data dir */
+export const getLogPath      = ()        => tauriInvoke("get_log_path_cmd", {});
+export const openLogs        = ()        => tauriInvoke("open_logs_cmd", {});
+
 /** External (Rust: *_cmd; no struct args) */
 export const openQuizlet     = ()        => tauriInvoke("open_quizlet_cmd", {});
+// Resolves to { path, markdown }; tokens also arrive as "notes://token" events.
+export const generateNotes   = (sessionId) =>
+  tauriInvoke("generate_notes_cmd", { args: { session_id: sessionId } });
+export const exportQuizletTsv = (sessionId) =>
+  tauriInvoke("export_quizlet_tsv_cmd", { args: { session_id: sessionId } });
+export const exportAnki      = (sessionId) =>
+  tauriInvoke("export_anki_cmd", { args: { session_id: sessionId } });